@@ -0,0 +1,6 @@
+alter table event.event_type drop constraint event_type_successor_event_type__name_fkey;
+alter table event.event_type drop column successor_event_type__name;
+alter table event.event_type drop column retired_at;
+alter table event.event_type drop column deprecated_at;
+alter table event.event_type drop constraint event_type_status_chk;
+alter table event.event_type drop column status;
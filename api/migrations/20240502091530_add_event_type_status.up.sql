@@ -0,0 +1,12 @@
+alter table event.event_type add column status text not null default 'active';
+alter table event.event_type add constraint event_type_status_chk check (status in ('active', 'deprecated', 'retired'));
+alter table event.event_type add column deprecated_at timestamptz default null;
+alter table event.event_type add column retired_at timestamptz default null;
+alter table event.event_type add column successor_event_type__name text default null;
+
+alter table event.event_type add constraint event_type_successor_event_type__name_fkey
+foreign key (application__id, successor_event_type__name)
+references event.event_type (application__id, event_type__name)
+match simple
+on delete set null (successor_event_type__name)
+on update cascade;
@@ -0,0 +1,4 @@
+-- 'E_EXPIRED' is kept in webhook.response_error as existing responses may reference it
+
+alter table event.event
+    drop column expires_at;
@@ -0,0 +1,6 @@
+alter table event.event
+    add column expires_at timestamptz default null;
+
+insert into webhook.response_error (response_error__name)
+values ('E_EXPIRED')
+on conflict (response_error__name) do nothing;
@@ -0,0 +1,3 @@
+drop index webhook.request_attempt_subscription__id_failed_at_idx;
+
+drop table webhook.replay_job;
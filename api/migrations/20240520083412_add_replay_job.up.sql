@@ -0,0 +1,36 @@
+create table webhook.replay_job
+(
+    replay_job__id uuid not null default public.gen_random_uuid(),
+    application__id uuid not null,
+    subscription__id uuid not null,
+    event_type__name text,
+    failed_from timestamptz not null,
+    failed_until timestamptz not null,
+    total_count integer not null,
+    enqueued_count integer not null default 0,
+    created_at timestamptz not null default statement_timestamp(),
+    created_by uuid,
+    started_at timestamptz,
+    completed_at timestamptz,
+    constraint replay_job_pkey primary key (replay_job__id),
+    constraint replay_job_failed_range_chk check (failed_from < failed_until)
+);
+
+alter table webhook.replay_job add constraint replay_job_application__id_fkey
+foreign key (application__id)
+references event.application (application__id)
+match simple
+on delete cascade
+on update cascade;
+
+alter table webhook.replay_job add constraint replay_job_subscription__id_fkey
+foreign key (subscription__id)
+references webhook.subscription (subscription__id)
+match simple
+on delete cascade
+on update cascade;
+
+create index replay_job_application__id_idx on webhook.replay_job (application__id);
+create index replay_job_pending_idx on webhook.replay_job (created_at) where completed_at is null;
+
+create index request_attempt_subscription__id_failed_at_idx on webhook.request_attempt (subscription__id, failed_at) where failed_at is not null;
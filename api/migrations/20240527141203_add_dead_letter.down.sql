@@ -0,0 +1 @@
+drop table webhook.dead_letter;
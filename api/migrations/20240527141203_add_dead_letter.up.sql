@@ -0,0 +1,42 @@
+create table webhook.dead_letter
+(
+    dead_letter__id uuid not null default public.gen_random_uuid(),
+    event__id uuid not null,
+    subscription__id uuid not null,
+    request_attempt__id uuid not null,
+    response__id uuid,
+    attempt_count integer not null,
+    created_at timestamptz not null default statement_timestamp(),
+    constraint dead_letter_pkey primary key (dead_letter__id),
+    constraint dead_letter_event__id_subscription__id_key unique (event__id, subscription__id)
+);
+
+alter table webhook.dead_letter add constraint dead_letter_event__id_fkey
+foreign key (event__id)
+references event.event (event__id)
+match simple
+on delete cascade
+on update cascade;
+
+alter table webhook.dead_letter add constraint dead_letter_subscription__id_fkey
+foreign key (subscription__id)
+references webhook.subscription (subscription__id)
+match simple
+on delete cascade
+on update cascade;
+
+alter table webhook.dead_letter add constraint dead_letter_request_attempt__id_fkey
+foreign key (request_attempt__id)
+references webhook.request_attempt (request_attempt__id)
+match simple
+on delete cascade
+on update cascade;
+
+alter table webhook.dead_letter add constraint dead_letter_response__id_fkey
+foreign key (response__id)
+references webhook.response (response__id)
+match simple
+on delete set null
+on update cascade;
+
+create index dead_letter_subscription__id_idx on webhook.dead_letter (subscription__id);
@@ -0,0 +1,3 @@
+drop index event.event_labels_idx;
+drop index event.event_application__id_event_type__name_received_at_idx;
+drop index event.event_application__id_received_at_idx;
@@ -0,0 +1,3 @@
+create index event_application__id_received_at_idx on event.event (application__id, received_at desc, event__id desc);
+create index event_application__id_event_type__name_received_at_idx on event.event (application__id, event_type__name, received_at desc, event__id desc);
+create index event_labels_idx on event.event using gin (labels jsonb_path_ops);
@@ -0,0 +1 @@
+drop index webhook.request_attempt_subscription__id_created_at_idx;
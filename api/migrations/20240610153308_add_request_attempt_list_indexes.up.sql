@@ -0,0 +1 @@
+create index request_attempt_subscription__id_created_at_idx on webhook.request_attempt (subscription__id, created_at desc, request_attempt__id desc);
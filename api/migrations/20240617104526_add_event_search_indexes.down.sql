@@ -0,0 +1,5 @@
+drop index event.event_payload_as_tsvector_idx;
+drop index event.event_payload_as_jsonb_idx;
+
+drop function event.payload_as_tsvector(bytea, text);
+drop function event.payload_as_jsonb(bytea, text);
@@ -0,0 +1,25 @@
+-- Payloads are stored as binary, these functions give indexable representations of text and JSON payloads
+-- (they are declared immutable because the database encoding, used by convert_from, never changes)
+
+create function event.payload_as_jsonb(payload bytea, payload_content_type text)
+    returns jsonb
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select case when payload_content_type = 'application/json' then convert_from(payload, 'UTF8')::jsonb end;
+$$;
+
+create function event.payload_as_tsvector(payload bytea, payload_content_type text)
+    returns tsvector
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select case when payload_content_type = 'text/plain' then to_tsvector('simple'::regconfig, convert_from(payload, 'UTF8')) end;
+$$;
+
+create index event_payload_as_jsonb_idx on event.event using gin (event.payload_as_jsonb(payload, payload_content_type) jsonb_path_ops);
+create index event_payload_as_tsvector_idx on event.event using gin (event.payload_as_tsvector(payload, payload_content_type));
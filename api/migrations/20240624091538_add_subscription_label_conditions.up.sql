@@ -0,0 +1,74 @@
+create table webhook.subscription__label_condition
+(
+    subscription__id uuid not null,
+    position smallint not null,
+    group_index smallint not null default 0,
+    label_key text not null,
+    operator text not null,
+    label_values text[] not null default array[]::text[],
+    constraint subscription__label_condition_pkey primary key (subscription__id, position),
+    constraint subscription__label_condition_operator_check check (operator in ('equals', 'exists', 'in')),
+    constraint subscription__label_condition_group_index_check check (group_index >= 0)
+);
+
+alter table webhook.subscription__label_condition add constraint subscription__label_condition_subscription__id_fkey
+foreign key (subscription__id)
+references webhook.subscription (subscription__id)
+match simple
+on delete cascade
+on update cascade;
+
+-- Label conditions are OR'ed groups of AND'ed conditions; a subscription without label conditions matches every event
+create function webhook.subscription_matches_label_conditions(subscription_id uuid, labels jsonb)
+    returns boolean
+    language sql
+    stable
+as
+$$
+    select not exists (
+        select 1
+        from webhook.subscription__label_condition as c
+        where c.subscription__id = subscription_id
+    ) or exists (
+        select 1
+        from webhook.subscription__label_condition as c
+        where c.subscription__id = subscription_id
+        group by c.group_index
+        having bool_and(coalesce(
+            case c.operator
+                when 'equals' then labels ->> c.label_key = c.label_values[1]
+                when 'exists' then labels ? c.label_key
+                when 'in' then labels ->> c.label_key = any(c.label_values)
+            end,
+            false
+        ))
+    );
+$$;
+
+create or replace function event.dispatch()
+    returns trigger
+    language plpgsql
+as
+$$
+declare
+    subscription_id uuid;
+begin
+    for subscription_id in
+        select s.subscription__id
+        from webhook.subscription as s
+        inner join webhook.subscription__event_type as set on set.subscription__id = s.subscription__id
+        where s.is_enabled
+          and s.application__id = new.application__id
+          and s.deleted_at is null
+          and set.event_type__name = new.event_type__name
+          and new.labels ->> s.label_key = s.label_value
+          and webhook.subscription_matches_label_conditions(s.subscription__id, new.labels)
+        loop
+            raise notice '[event %] matching subscription: %', new.event__id, subscription_id;
+            insert into webhook.request_attempt (event__id, subscription__id, delay_until)
+            values (new.event__id, subscription_id, new.deliver_at);
+        end loop;
+    update event.event set dispatched_at = statement_timestamp() where event__id = new.event__id;
+    return new;
+end;
+$$;
@@ -0,0 +1,53 @@
+create or replace function event.dispatch()
+    returns trigger
+    language plpgsql
+as
+$$
+declare
+    service_name text;
+    resource_type_name text;
+    verb_name text;
+    subscription_id uuid;
+begin
+    select et.service__name, et.resource_type__name, et.verb__name
+    into service_name, resource_type_name, verb_name
+    from event.event_type as et
+    where et.application__id = new.application__id
+      and et.event_type__name = new.event_type__name;
+
+    for subscription_id in
+        select s.subscription__id
+        from webhook.subscription as s
+        where s.is_enabled
+          and s.application__id = new.application__id
+          and s.deleted_at is null
+          and (
+            exists (
+                select 1
+                from webhook.subscription__event_type as set
+                where set.subscription__id = s.subscription__id
+                  and set.event_type__name = new.event_type__name
+            ) or exists (
+                select 1
+                from webhook.subscription__event_type_pattern as setp
+                where setp.subscription__id = s.subscription__id
+                  and coalesce(setp.service__name = service_name, true)
+                  and coalesce(setp.resource_type__name = resource_type_name, true)
+                  and coalesce(setp.verb__name = verb_name, true)
+            )
+          )
+          and new.labels ->> s.label_key = s.label_value
+          and webhook.subscription_matches_label_conditions(s.subscription__id, new.labels)
+        loop
+            raise notice '[event %] matching subscription: %', new.event__id, subscription_id;
+            insert into webhook.request_attempt (event__id, subscription__id, delay_until)
+            values (new.event__id, subscription_id, new.deliver_at);
+        end loop;
+    update event.event set dispatched_at = statement_timestamp() where event__id = new.event__id;
+    return new;
+end;
+$$;
+
+drop function event.filter_document(bytea, text, jsonb, jsonb);
+
+alter table webhook.subscription drop column filter;
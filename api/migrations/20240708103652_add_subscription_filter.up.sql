@@ -0,0 +1,74 @@
+alter table webhook.subscription add column filter text default null;
+
+-- Document against which subscription filters are evaluated
+create function event.filter_document(payload bytea, payload_content_type text, labels jsonb, metadata jsonb)
+    returns jsonb
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select jsonb_build_object(
+    'payload', case payload_content_type
+        when 'application/json' then event.payload_as_jsonb(payload, payload_content_type)
+        when 'text/plain' then to_jsonb(convert_from(payload, 'UTF8'))
+        else 'null'::jsonb
+    end,
+    'labels', labels,
+    'metadata', coalesce(metadata, jsonb_build_object())
+);
+$$;
+
+create or replace function event.dispatch()
+    returns trigger
+    language plpgsql
+as
+$$
+declare
+    service_name text;
+    resource_type_name text;
+    verb_name text;
+    filter_document jsonb;
+    subscription_id uuid;
+begin
+    select et.service__name, et.resource_type__name, et.verb__name
+    into service_name, resource_type_name, verb_name
+    from event.event_type as et
+    where et.application__id = new.application__id
+      and et.event_type__name = new.event_type__name;
+
+    filter_document := event.filter_document(new.payload, new.payload_content_type, new.labels, new.metadata);
+
+    for subscription_id in
+        select s.subscription__id
+        from webhook.subscription as s
+        where s.is_enabled
+          and s.application__id = new.application__id
+          and s.deleted_at is null
+          and (
+            exists (
+                select 1
+                from webhook.subscription__event_type as set
+                where set.subscription__id = s.subscription__id
+                  and set.event_type__name = new.event_type__name
+            ) or exists (
+                select 1
+                from webhook.subscription__event_type_pattern as setp
+                where setp.subscription__id = s.subscription__id
+                  and coalesce(setp.service__name = service_name, true)
+                  and coalesce(setp.resource_type__name = resource_type_name, true)
+                  and coalesce(setp.verb__name = verb_name, true)
+            )
+          )
+          and new.labels ->> s.label_key = s.label_value
+          and webhook.subscription_matches_label_conditions(s.subscription__id, new.labels)
+          and (s.filter is null or coalesce(jsonb_path_match(filter_document, s.filter::jsonpath, jsonb_build_object(), true), false))
+        loop
+            raise notice '[event %] matching subscription: %', new.event__id, subscription_id;
+            insert into webhook.request_attempt (event__id, subscription__id, delay_until)
+            values (new.event__id, subscription_id, new.deliver_at);
+        end loop;
+    update event.event set dispatched_at = statement_timestamp() where event__id = new.event__id;
+    return new;
+end;
+$$;
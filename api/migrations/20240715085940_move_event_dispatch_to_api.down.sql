@@ -0,0 +1,88 @@
+drop index event.event_undispatched_idx;
+
+-- Label conditions are OR'ed groups of AND'ed conditions; a subscription without label conditions matches every event
+create function webhook.subscription_matches_label_conditions(subscription_id uuid, labels jsonb)
+    returns boolean
+    language sql
+    stable
+as
+$$
+    select not exists (
+        select 1
+        from webhook.subscription__label_condition as c
+        where c.subscription__id = subscription_id
+    ) or exists (
+        select 1
+        from webhook.subscription__label_condition as c
+        where c.subscription__id = subscription_id
+        group by c.group_index
+        having bool_and(coalesce(
+            case c.operator
+                when 'equals' then labels ->> c.label_key = c.label_values[1]
+                when 'exists' then labels ? c.label_key
+                when 'in' then labels ->> c.label_key = any(c.label_values)
+            end,
+            false
+        ))
+    );
+$$;
+
+create or replace function event.dispatch()
+    returns trigger
+    language plpgsql
+as
+$$
+declare
+    service_name text;
+    resource_type_name text;
+    verb_name text;
+    filter_document jsonb;
+    subscription_id uuid;
+begin
+    select et.service__name, et.resource_type__name, et.verb__name
+    into service_name, resource_type_name, verb_name
+    from event.event_type as et
+    where et.application__id = new.application__id
+      and et.event_type__name = new.event_type__name;
+
+    filter_document := event.filter_document(new.payload, new.payload_content_type, new.labels, new.metadata);
+
+    for subscription_id in
+        select s.subscription__id
+        from webhook.subscription as s
+        where s.is_enabled
+          and s.application__id = new.application__id
+          and s.deleted_at is null
+          and (
+            exists (
+                select 1
+                from webhook.subscription__event_type as set
+                where set.subscription__id = s.subscription__id
+                  and set.event_type__name = new.event_type__name
+            ) or exists (
+                select 1
+                from webhook.subscription__event_type_pattern as setp
+                where setp.subscription__id = s.subscription__id
+                  and coalesce(setp.service__name = service_name, true)
+                  and coalesce(setp.resource_type__name = resource_type_name, true)
+                  and coalesce(setp.verb__name = verb_name, true)
+            )
+          )
+          and new.labels ->> s.label_key = s.label_value
+          and webhook.subscription_matches_label_conditions(s.subscription__id, new.labels)
+          and (s.filter is null or coalesce(jsonb_path_match(filter_document, s.filter::jsonpath, jsonb_build_object(), true), false))
+        loop
+            raise notice '[event %] matching subscription: %', new.event__id, subscription_id;
+            insert into webhook.request_attempt (event__id, subscription__id, delay_until)
+            values (new.event__id, subscription_id, new.deliver_at);
+        end loop;
+    update event.event set dispatched_at = statement_timestamp() where event__id = new.event__id;
+    return new;
+end;
+$$;
+
+create trigger event_dispatch
+    after insert
+    on event.event
+    for each row
+    execute function event.dispatch();
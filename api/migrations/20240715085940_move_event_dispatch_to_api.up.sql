@@ -0,0 +1,6 @@
+-- Events are now dispatched by the API, which looks for events that were not dispatched yet
+drop trigger event_dispatch on event.event;
+drop function event.dispatch();
+drop function webhook.subscription_matches_label_conditions(uuid, jsonb);
+
+create index event_undispatched_idx on event.event (received_at) where dispatched_at is null;
@@ -0,0 +1 @@
+alter table webhook.subscription drop column transformation;
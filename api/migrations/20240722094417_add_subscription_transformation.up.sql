@@ -0,0 +1 @@
+alter table webhook.subscription add column transformation jsonb default null;
@@ -0,0 +1,4 @@
+drop index webhook.request_attempt_subscription__id_unfinished_idx;
+
+alter table webhook.subscription drop column ordering_key_label;
+alter table webhook.subscription drop column ordered_delivery;
@@ -0,0 +1,5 @@
+alter table webhook.subscription add column ordered_delivery boolean not null default false;
+alter table webhook.subscription add column ordering_key_label text default null;
+
+-- Used by workers to check whether an earlier event of an ordered subscription is still being delivered
+create index request_attempt_subscription__id_unfinished_idx on webhook.request_attempt (subscription__id) where succeeded_at is null and failed_at is null;
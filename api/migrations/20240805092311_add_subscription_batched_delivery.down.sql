@@ -0,0 +1,3 @@
+alter table webhook.subscription drop column batch_linger_in_ms;
+alter table webhook.subscription drop column batch_max_size_in_bytes;
+alter table webhook.subscription drop column batch_max_count;
@@ -0,0 +1,3 @@
+alter table webhook.subscription add column batch_max_count integer default null;
+alter table webhook.subscription add column batch_max_size_in_bytes integer default null;
+alter table webhook.subscription add column batch_linger_in_ms integer not null default 1000;
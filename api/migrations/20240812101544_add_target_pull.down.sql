@@ -0,0 +1,4 @@
+drop index webhook.request_attempt_subscription__id_picked_at_unfinished_idx;
+
+delete from webhook.subscription where target__id in (select target__id from webhook.target_pull);
+drop table webhook.target_pull;
@@ -0,0 +1,17 @@
+create table webhook.target_pull
+(
+    target__id uuid not null default public.gen_random_uuid(),
+    visibility_timeout_in_s integer not null default 30,
+    constraint target_pull_visibility_timeout_in_s_chk check (visibility_timeout_in_s between 1 and 43200)
+)
+inherits (webhook.target);
+
+alter table webhook.target_pull add constraint target_pull_target__id_fkey
+foreign key (target__id)
+references webhook.subscription (target__id)
+match simple
+on delete cascade
+on update cascade;
+
+-- Used when consumers fetch events of pull subscriptions
+create index request_attempt_subscription__id_picked_at_unfinished_idx on webhook.request_attempt (subscription__id, picked_at) where succeeded_at is null and failed_at is null;
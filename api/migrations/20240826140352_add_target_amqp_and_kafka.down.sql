@@ -0,0 +1,7 @@
+drop view webhook.target_definition;
+
+delete from webhook.subscription where target__id in (select target__id from webhook.target_kafka);
+drop table webhook.target_kafka;
+
+delete from webhook.subscription where target__id in (select target__id from webhook.target_amqp);
+drop table webhook.target_amqp;
@@ -0,0 +1,72 @@
+create table webhook.target_amqp
+(
+    target__id uuid not null default public.gen_random_uuid(),
+    url text not null,
+    exchange text not null,
+    routing_key text not null default '',
+    routing_key_label text
+)
+inherits (webhook.target);
+
+alter table webhook.target_amqp add constraint target_amqp_target__id_fkey
+foreign key (target__id)
+references webhook.subscription (target__id)
+match simple
+on delete cascade
+on update cascade;
+
+create table webhook.target_kafka
+(
+    target__id uuid not null default public.gen_random_uuid(),
+    brokers text[] not null,
+    topic text not null,
+    partition_key_label text,
+    sasl_username text,
+    sasl_password text,
+    tls boolean not null default false,
+    constraint target_kafka_sasl_chk check ((sasl_username is null) = (sasl_password is null))
+)
+inherits (webhook.target);
+
+alter table webhook.target_kafka add constraint target_kafka_target__id_fkey
+foreign key (target__id)
+references webhook.subscription (target__id)
+match simple
+on delete cascade
+on update cascade;
+
+-- Definition of every target as JSON, whatever its type
+create view webhook.target_definition as
+    select target__id, 'http' as target_type, jsonb_build_object(
+        'type', 'http',
+        'method', method,
+        'url', url,
+        'headers', headers
+    ) as target
+    from webhook.target_http
+    union all
+    select target__id, 'pull' as target_type, jsonb_build_object(
+        'type', 'pull',
+        'visibility_timeout_in_s', visibility_timeout_in_s
+    ) as target
+    from webhook.target_pull
+    union all
+    select target__id, 'amqp' as target_type, jsonb_build_object(
+        'type', 'amqp',
+        'url', url,
+        'exchange', exchange,
+        'routing_key', routing_key,
+        'routing_key_label', routing_key_label
+    ) as target
+    from webhook.target_amqp
+    union all
+    select target__id, 'kafka' as target_type, jsonb_build_object(
+        'type', 'kafka',
+        'brokers', brokers,
+        'topic', topic,
+        'partition_key_label', partition_key_label,
+        'sasl_username', sasl_username,
+        'sasl_password', sasl_password,
+        'tls', tls
+    ) as target
+    from webhook.target_kafka;
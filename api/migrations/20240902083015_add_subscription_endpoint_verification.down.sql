@@ -0,0 +1,8 @@
+drop index webhook.subscription_endpoint_verification_pending_idx;
+
+alter table webhook.subscription
+    drop column endpoint_verification_status,
+    drop column endpoint_verification_challenge,
+    drop column endpoint_verification_checked_at,
+    drop column endpoint_verification_error,
+    drop column enable_on_endpoint_verification;
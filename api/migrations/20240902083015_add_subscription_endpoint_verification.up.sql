@@ -0,0 +1,10 @@
+alter table webhook.subscription
+    add column endpoint_verification_status text,
+    add column endpoint_verification_challenge text,
+    add column endpoint_verification_checked_at timestamptz,
+    add column endpoint_verification_error text,
+    add column enable_on_endpoint_verification boolean not null default false,
+    add constraint subscription_endpoint_verification_status_chk check (endpoint_verification_status in ('pending', 'verified', 'failed')),
+    add constraint subscription_endpoint_verification_challenge_chk check (endpoint_verification_status is distinct from 'pending' or endpoint_verification_challenge is not null);
+
+create index subscription_endpoint_verification_pending_idx on webhook.subscription (subscription__id) where endpoint_verification_status = 'pending' and deleted_at is null;
@@ -0,0 +1,2 @@
+alter table webhook.subscription
+    add column is_paused boolean not null default false;
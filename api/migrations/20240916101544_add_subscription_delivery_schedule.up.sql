@@ -0,0 +1,2 @@
+alter table webhook.subscription
+    add column delivery_schedule jsonb;
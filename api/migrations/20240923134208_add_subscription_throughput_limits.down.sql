@@ -0,0 +1,5 @@
+drop table webhook.subscription__delivery_rate;
+
+alter table webhook.subscription
+    drop column max_requests_per_second,
+    drop column max_in_flight_requests;
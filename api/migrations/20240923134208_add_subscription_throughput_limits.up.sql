@@ -0,0 +1,21 @@
+alter table webhook.subscription
+    add column max_requests_per_second integer,
+    add column max_in_flight_requests integer,
+    add constraint subscription_max_requests_per_second_chk check (max_requests_per_second between 1 and 10000),
+    add constraint subscription_max_in_flight_requests_chk check (max_in_flight_requests between 1 and 1000);
+
+-- Number of requests sent to throughput-limited subscriptions during the current second, shared by all workers
+create table webhook.subscription__delivery_rate
+(
+    subscription__id uuid not null,
+    window_started_at timestamptz not null,
+    request_count integer not null,
+    constraint subscription__delivery_rate_pkey primary key (subscription__id)
+);
+
+alter table webhook.subscription__delivery_rate add constraint subscription__delivery_rate_subscription__id_fkey
+foreign key (subscription__id)
+references webhook.subscription (subscription__id)
+match simple
+on delete cascade
+on update cascade;
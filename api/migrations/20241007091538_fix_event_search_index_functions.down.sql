@@ -0,0 +1,21 @@
+create or replace function event.payload_as_jsonb(payload bytea, payload_content_type text)
+    returns jsonb
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select case when payload_content_type = 'application/json' then convert_from(payload, 'UTF8')::jsonb end;
+$$;
+
+create or replace function event.payload_as_tsvector(payload bytea, payload_content_type text)
+    returns tsvector
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select case when payload_content_type = 'text/plain' then to_tsvector('simple'::regconfig, convert_from(payload, 'UTF8')) end;
+$$;
+
+reindex index event.event_payload_as_tsvector_idx;
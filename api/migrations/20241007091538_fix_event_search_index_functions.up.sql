@@ -0,0 +1,34 @@
+-- Some payloads made ingestion fail because they cannot be represented in the search indexes; such payloads are now excluded from the indexes instead:
+-- - JSON payloads containing \u0000, which jsonb does not support (the escape sequence is looked for in the text, so a few payloads that only contain an escaped backslash followed by "u0000" are not indexed either)
+-- - text payloads containing NUL bytes, which cannot be converted to text
+-- - large text payloads, whose tsvector could exceed the maximum size of a tsvector (1 MB)
+-- Payloads are validated when they are ingested, so other conversion errors cannot happen
+
+create or replace function event.payload_as_jsonb(payload bytea, payload_content_type text)
+    returns jsonb
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select case
+    when payload_content_type = 'application/json' and position('\x00'::bytea in payload) = 0 and position('\u0000' in convert_from(payload, 'UTF8')) = 0
+    then convert_from(payload, 'UTF8')::jsonb
+end;
+$$;
+
+create or replace function event.payload_as_tsvector(payload bytea, payload_content_type text)
+    returns tsvector
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select case
+    when payload_content_type = 'text/plain' and position('\x00'::bytea in payload) = 0 and octet_length(payload) <= 262144
+    then to_tsvector('simple'::regconfig, convert_from(payload, 'UTF8'))
+end;
+$$;
+
+-- Large text payloads that were indexed before must be removed from the index
+reindex index event.event_payload_as_tsvector_idx;
@@ -0,0 +1,6 @@
+drop index event.event_undispatched_idx;
+create index event_undispatched_idx on event.event (received_at) where dispatched_at is null;
+
+alter table event.event
+    drop column dispatch_failed_at,
+    drop column dispatch_attempt_count;
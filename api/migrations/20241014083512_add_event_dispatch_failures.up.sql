@@ -0,0 +1,7 @@
+-- Events that could not be dispatched after several attempts are set aside (dispatch_failed_at) so they do not block others
+alter table event.event
+    add column dispatch_attempt_count smallint not null default 0,
+    add column dispatch_failed_at timestamptz;
+
+drop index event.event_undispatched_idx;
+create index event_undispatched_idx on event.event (received_at) where dispatched_at is null and dispatch_failed_at is null;
@@ -0,0 +1,2 @@
+alter table event.event
+    drop column delivery_cancelled_at;
@@ -0,0 +1,3 @@
+-- Scheduled deliveries can be cancelled before the event is dispatched; the dispatcher then creates no request attempt
+alter table event.event
+    add column delivery_cancelled_at timestamptz;
@@ -0,0 +1,2 @@
+alter table webhook.subscription
+    drop column throughput_lock_id;
@@ -0,0 +1,3 @@
+-- Workers take advisory locks keyed on this ID to count requests in flight to throughput-limited subscriptions (lock key: throughput_lock_id * 1000 + slot)
+alter table webhook.subscription
+    add column throughput_lock_id bigint generated always as identity;
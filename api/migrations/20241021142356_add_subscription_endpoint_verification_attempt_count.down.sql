@@ -0,0 +1,2 @@
+alter table webhook.subscription
+    drop column endpoint_verification_attempt_count;
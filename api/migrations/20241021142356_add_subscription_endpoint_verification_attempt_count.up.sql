@@ -0,0 +1,3 @@
+-- Transient failures of endpoint verification are retried a few times before the verification fails
+alter table webhook.subscription
+    add column endpoint_verification_attempt_count integer not null default 0;
@@ -0,0 +1,490 @@
+use actix::clock::sleep;
+use chrono::{DateTime, Utc};
+use log::{debug, error, trace, warn};
+use serde_json::Value;
+use sqlx::{query, query_as, Connection, PgPool, Postgres, Transaction};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::handlers::subscriptions::{LabelCondition, LabelConditionOperator};
+use crate::validators::EVENT_TYPE_PATTERN_WILDCARD;
+
+/// Number of times the dispatch of an event is attempted before it is set aside
+const MAX_DISPATCH_ATTEMPTS: i16 = 5;
+
+/// Look for undispatched events and create request attempts for the subscriptions they match
+pub async fn periodically_dispatch_events(db: &PgPool, period: Duration, batch_size: u16) {
+    loop {
+        match dispatch_next_events(db, batch_size).await {
+            // A full batch means that there may be more events waiting, so we do not wait before looking again
+            Ok(dispatched) if dispatched >= usize::from(batch_size) => continue,
+            Ok(_) => (),
+            Err(e) => error!("Could not dispatch events: {e}"),
+        }
+
+        sleep(period).await;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct UndispatchedEvent {
+    event_id: Uuid,
+    application_id: Uuid,
+    service_name: String,
+    resource_type_name: String,
+    verb_name: String,
+    event_type_name: String,
+    labels: Value,
+    received_at: DateTime<Utc>,
+    deliver_at: Option<DateTime<Utc>>,
+    delivery_cancelled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+struct DispatchableSubscription {
+    subscription_id: Uuid,
+    application_id: Uuid,
+    event_types: HashSet<String>,
+    event_type_patterns: Vec<String>,
+    label_key: String,
+    label_value: String,
+    label_conditions: Vec<LabelCondition>,
+    filter: Option<String>,
+}
+
+impl DispatchableSubscription {
+    /// Everything but the filter, which is evaluated by the database
+    fn matches(&self, event: &UndispatchedEvent) -> bool {
+        self.application_id == event.application_id
+            && self.matches_event_type(event)
+            && label(&event.labels, &self.label_key) == Some(self.label_value.as_str())
+            && matches_label_conditions(&self.label_conditions, &event.labels)
+    }
+
+    fn matches_event_type(&self, event: &UndispatchedEvent) -> bool {
+        self.event_types.contains(&event.event_type_name)
+            || self.event_type_patterns.iter().any(|pattern| {
+                let names = [
+                    event.service_name.as_str(),
+                    event.resource_type_name.as_str(),
+                    event.verb_name.as_str(),
+                ];
+                pattern.split('.').count() == names.len()
+                    && pattern
+                        .split('.')
+                        .zip(names)
+                        .all(|(part, name)| part == EVENT_TYPE_PATTERN_WILDCARD || part == name)
+            })
+    }
+}
+
+fn label<'a>(labels: &'a Value, key: &str) -> Option<&'a str> {
+    labels.get(key).and_then(|v| v.as_str())
+}
+
+/// Label conditions are OR'ed groups of AND'ed conditions; no label conditions at all means that every event matches
+fn matches_label_conditions(conditions: &[LabelCondition], labels: &Value) -> bool {
+    let mut groups: HashMap<i16, bool> = HashMap::new();
+    for condition in conditions {
+        let value = label(labels, &condition.label_key);
+        let satisfied = match condition.operator {
+            LabelConditionOperator::Equals => {
+                value.is_some() && value == condition.values.first().map(|v| v.as_str())
+            }
+            LabelConditionOperator::Exists => labels.get(&condition.label_key).is_some(),
+            LabelConditionOperator::In => {
+                value.is_some_and(|value| condition.values.iter().any(|v| v == value))
+            }
+        };
+        *groups.entry(condition.group).or_insert(true) &= satisfied;
+    }
+
+    groups.is_empty() || groups.values().any(|satisfied| *satisfied)
+}
+
+/// Dispatch the oldest undispatched events; returns the number of dispatched events
+async fn dispatch_next_events(db: &PgPool, batch_size: u16) -> Result<usize, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let events = query_as!(
+        UndispatchedEvent,
+        "
+            SELECT e.event__id AS event_id, e.application__id AS application_id, et.service__name AS service_name, et.resource_type__name AS resource_type_name, et.verb__name AS verb_name, e.event_type__name AS event_type_name, e.labels, e.received_at, e.deliver_at, e.delivery_cancelled_at
+            FROM event.event AS e
+            INNER JOIN event.event_type AS et ON et.application__id = e.application__id AND et.event_type__name = e.event_type__name
+            WHERE e.dispatched_at IS NULL AND e.dispatch_failed_at IS NULL
+            ORDER BY e.received_at ASC
+            LIMIT $1
+            FOR UPDATE OF e SKIP LOCKED
+        ",
+        i64::from(batch_size),
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if events.is_empty() {
+        trace!("No event to dispatch");
+        tx.commit().await?;
+        return Ok(0);
+    }
+
+    let subscriptions = fetch_subscriptions(
+        &mut tx,
+        &events
+            .iter()
+            .map(|e| e.application_id)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>(),
+    )
+    .await?;
+
+    let mut request_attempts = (vec![], vec![], vec![]);
+    let mut failed_event_ids = vec![];
+    for event in &events {
+        if event.delivery_cancelled_at.is_some() {
+            trace!(
+                "[event {}] delivery was cancelled before dispatch",
+                event.event_id
+            );
+            continue;
+        }
+
+        let matching_subscriptions = subscriptions
+            .iter()
+            .filter(|s| s.matches(event))
+            .collect::<Vec<_>>();
+
+        // Each event is filtered inside a savepoint so that an event on which the database fails does not block the others; it will be retried in a later batch
+        let mut savepoint = (*tx).begin().await?;
+        let subscription_ids =
+            match filter_subscriptions(&mut savepoint, event, &matching_subscriptions).await {
+                Ok(subscription_ids) => {
+                    savepoint.commit().await?;
+                    subscription_ids
+                }
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    warn!(
+                        "[event {}] could not be dispatched (it will be retried): {e}",
+                        event.event_id
+                    );
+                    failed_event_ids.push(event.event_id);
+                    continue;
+                }
+            };
+
+        for subscription_id in subscription_ids {
+            trace!(
+                "[event {}] matching subscription: {subscription_id}",
+                event.event_id
+            );
+            request_attempts.0.push(event.event_id);
+            request_attempts.1.push(subscription_id);
+            request_attempts.2.push(event.deliver_at);
+        }
+    }
+
+    // Request attempts are created and events are marked as dispatched atomically
+    query!(
+        "
+            INSERT INTO webhook.request_attempt (event__id, subscription__id, delay_until)
+            SELECT * FROM UNNEST($1::uuid[], $2::uuid[], $3::timestamptz[])
+        ",
+        &request_attempts.0,
+        &request_attempts.1,
+        &request_attempts.2 as &[Option<DateTime<Utc>>],
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(
+        "
+            UPDATE event.event
+            SET dispatched_at = statement_timestamp()
+            WHERE event__id = ANY($1)
+        ",
+        &events
+            .iter()
+            .map(|e| e.event_id)
+            .filter(|id| !failed_event_ids.contains(id))
+            .collect::<Vec<_>>(),
+    )
+    .execute(&mut *tx)
+    .await?;
+    let set_aside_events = query!(
+        "
+            UPDATE event.event
+            SET dispatch_attempt_count = dispatch_attempt_count + 1,
+                dispatch_failed_at = CASE WHEN dispatch_attempt_count + 1 >= $2::smallint THEN statement_timestamp() END
+            WHERE event__id = ANY($1)
+            RETURNING event__id, dispatch_failed_at
+        ",
+        &failed_event_ids,
+        MAX_DISPATCH_ATTEMPTS,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for event in set_aside_events
+        .into_iter()
+        .filter(|e| e.dispatch_failed_at.is_some())
+    {
+        error!(
+            "[event {}] could not be dispatched after {MAX_DISPATCH_ATTEMPTS} attempts and was set aside",
+            event.event__id
+        );
+    }
+
+    tx.commit().await?;
+
+    let max_lag = events
+        .iter()
+        .map(|e| Utc::now() - e.received_at)
+        .max()
+        .unwrap_or_default();
+    debug!(
+        "Dispatched {} events into {} request attempts ({} events could not be dispatched; max dispatch lag: {} ms)",
+        events.len() - failed_event_ids.len(),
+        request_attempts.0.len(),
+        failed_event_ids.len(),
+        max_lag.num_milliseconds()
+    );
+    Ok(events.len())
+}
+
+async fn fetch_subscriptions(
+    tx: &mut Transaction<'_, Postgres>,
+    application_ids: &[Uuid],
+) -> Result<Vec<DispatchableSubscription>, sqlx::Error> {
+    #[allow(non_snake_case)]
+    struct RawSubscription {
+        subscription__id: Uuid,
+        application__id: Uuid,
+        event_types: Vec<String>,
+        event_type_patterns: Vec<String>,
+        label_key: String,
+        label_value: String,
+        label_conditions: Option<Value>,
+        filter: Option<String>,
+    }
+    let subscriptions = query_as!(
+        RawSubscription,
+        r#"
+            SELECT s.subscription__id, s.application__id,
+                ARRAY(
+                    SELECT set.event_type__name
+                    FROM webhook.subscription__event_type AS set
+                    WHERE set.subscription__id = s.subscription__id
+                ) AS "event_types!",
+                ARRAY(
+                    SELECT setp.pattern
+                    FROM webhook.subscription__event_type_pattern AS setp
+                    WHERE setp.subscription__id = s.subscription__id
+                ) AS "event_type_patterns!",
+                s.label_key, s.label_value,
+                (
+                    SELECT jsonb_agg(jsonb_build_object('group', c.group_index, 'label_key', c.label_key, 'operator', c.operator, 'values', c.label_values) ORDER BY c.position)
+                    FROM webhook.subscription__label_condition AS c
+                    WHERE c.subscription__id = s.subscription__id
+                ) AS label_conditions,
+                s.filter
+            FROM webhook.subscription AS s
+            WHERE s.application__id = ANY($1) AND s.is_enabled AND s.deleted_at IS NULL
+        "#,
+        application_ids,
+    )
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .filter_map(|s| {
+        // A subscription whose label conditions cannot be parsed must not receive events it may not want; it is skipped for this batch
+        let label_conditions = match s.label_conditions.map(serde_json::from_value) {
+            Some(Ok(label_conditions)) => label_conditions,
+            Some(Err(e)) => {
+                error!(
+                    "[subscription {}] label conditions could not be parsed, the subscription is skipped: {e}",
+                    s.subscription__id
+                );
+                return None;
+            }
+            None => Vec::new(),
+        };
+
+        Some(DispatchableSubscription {
+            subscription_id: s.subscription__id,
+            application_id: s.application__id,
+            event_types: HashSet::from_iter(s.event_types),
+            event_type_patterns: s.event_type_patterns,
+            label_key: s.label_key,
+            label_value: s.label_value,
+            label_conditions,
+            filter: s.filter,
+        })
+    })
+    .collect();
+
+    Ok(subscriptions)
+}
+
+/// Keep subscriptions that have no filter or whose filter matches the event
+///
+/// Filters are evaluated together; if the database fails on them, they are evaluated one by one so that a failing filter only counts as not matching.
+async fn filter_subscriptions(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &UndispatchedEvent,
+    subscriptions: &[&DispatchableSubscription],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let (filtered, unfiltered): (Vec<&DispatchableSubscription>, Vec<_>) =
+        subscriptions.iter().partition(|s| s.filter.is_some());
+    let mut subscription_ids = unfiltered
+        .into_iter()
+        .map(|s| s.subscription_id)
+        .collect::<Vec<_>>();
+
+    if filtered.is_empty() {
+        return Ok(subscription_ids);
+    }
+
+    let mut savepoint = (**tx).begin().await?;
+    match matching_filters(&mut savepoint, event, &filtered).await {
+        Ok(filtered_subscription_ids) => {
+            savepoint.commit().await?;
+            subscription_ids.extend(filtered_subscription_ids);
+        }
+        Err(sqlx::Error::Database(_)) => {
+            savepoint.rollback().await?;
+            for subscription in filtered {
+                let mut savepoint = (**tx).begin().await?;
+                match matching_filters(&mut savepoint, event, &[subscription]).await {
+                    Ok(filtered_subscription_ids) => {
+                        savepoint.commit().await?;
+                        subscription_ids.extend(filtered_subscription_ids);
+                    }
+                    Err(sqlx::Error::Database(e)) => {
+                        savepoint.rollback().await?;
+                        warn!(
+                            "[event {}] filter of subscription {} could not be evaluated and is considered as not matching: {e}",
+                            event.event_id, subscription.subscription_id
+                        );
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Err(e) => return Err(e),
+    }
+
+    Ok(subscription_ids)
+}
+
+async fn matching_filters(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &UndispatchedEvent,
+    subscriptions: &[&DispatchableSubscription],
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let subscription_ids = query!(
+        r#"
+            SELECT f.subscription__id AS "subscription__id!"
+            FROM event.event AS e, UNNEST($2::uuid[], $3::text[]) AS f(subscription__id, filter)
+            WHERE e.event__id = $1
+                AND COALESCE(jsonb_path_match(event.filter_document(e.payload, e.payload_content_type, e.labels, e.metadata), f.filter::jsonpath, jsonb_build_object(), true), false)
+        "#,
+        &event.event_id,
+        &subscriptions
+            .iter()
+            .map(|s| s.subscription_id)
+            .collect::<Vec<_>>(),
+        &subscriptions
+            .iter()
+            .map(|s| s.filter.to_owned().unwrap_or_default())
+            .collect::<Vec<_>>(),
+    )
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .map(|s| s.subscription__id)
+    .collect();
+
+    Ok(subscription_ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn event(event_type: &str, labels: Value) -> UndispatchedEvent {
+        let mut names = event_type.split('.').map(|n| n.to_owned());
+        UndispatchedEvent {
+            event_id: Uuid::nil(),
+            application_id: Uuid::nil(),
+            service_name: names.next().unwrap(),
+            resource_type_name: names.next().unwrap(),
+            verb_name: names.next().unwrap(),
+            event_type_name: event_type.to_owned(),
+            labels,
+            received_at: Utc::now(),
+            deliver_at: None,
+            delivery_cancelled_at: None,
+        }
+    }
+
+    fn subscription(event_types: &[&str], patterns: &[&str]) -> DispatchableSubscription {
+        DispatchableSubscription {
+            subscription_id: Uuid::nil(),
+            application_id: Uuid::nil(),
+            event_types: event_types.iter().map(|et| et.to_string()).collect(),
+            event_type_patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            label_key: "tenant".to_owned(),
+            label_value: "acme".to_owned(),
+            label_conditions: vec![],
+            filter: None,
+        }
+    }
+
+    fn condition(group: i16, operator: LabelConditionOperator, values: &[&str]) -> LabelCondition {
+        LabelCondition {
+            group,
+            label_key: "env".to_owned(),
+            operator,
+            values: values.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn match_event_types() {
+        let labels = json!({ "tenant": "acme" });
+        let s = subscription(&["billing.invoice.paid"], &["*.order.*"]);
+
+        assert!(s.matches(&event("billing.invoice.paid", labels.clone())));
+        assert!(s.matches(&event("shop.order.created", labels.clone())));
+        assert!(!s.matches(&event("billing.invoice.created", labels.clone())));
+        assert!(!s.matches(&event("billing.invoice.paid", json!({ "tenant": "other" }))));
+        assert!(!s.matches(&event("billing.invoice.paid", json!({}))));
+    }
+
+    #[test]
+    fn match_label_conditions() {
+        let conditions = [
+            condition(0, LabelConditionOperator::In, &["prod", "staging"]),
+            condition(0, LabelConditionOperator::Exists, &[]),
+            condition(1, LabelConditionOperator::Equals, &["dev"]),
+        ];
+
+        assert!(matches_label_conditions(&[], &json!({})));
+        assert!(matches_label_conditions(
+            &conditions,
+            &json!({ "env": "prod" })
+        ));
+        assert!(matches_label_conditions(
+            &conditions,
+            &json!({ "env": "dev" })
+        ));
+        assert!(!matches_label_conditions(
+            &conditions,
+            &json!({ "env": "test" })
+        ));
+        assert!(!matches_label_conditions(&conditions, &json!({})));
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Utc};
+use paperclip::actix::{
+    api_v2_operation,
+    web::{Data, Json, Query},
+    Apiv2Schema,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as};
+use uuid::Uuid;
+
+use crate::iam::{AuthProof, Role};
+use crate::openapi::OaApplicationSecret;
+use crate::problems::Hook0Problem;
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct DeadLetter {
+    pub dead_letter_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type_name: String,
+    pub subscription_id: Uuid,
+    pub last_request_attempt_id: Uuid,
+    pub last_response_id: Option<Uuid>,
+    pub last_response_error_name: Option<String>,
+    pub last_response_http_code: Option<i16>,
+    pub attempt_count: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct Qs {
+    application_id: Uuid,
+    subscription_id: Option<Uuid>,
+    event_type_name: Option<String>,
+}
+
+#[api_v2_operation(
+    summary = "List dead letters",
+    description = "Dead letters are deliveries of events to subscriptions that were given up after all retries failed. The 100 most recent ones are returned.",
+    operation_id = "deadLetters.list",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn list(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    qs: Query<Qs>,
+) -> Result<Json<Vec<DeadLetter>>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &qs.application_id, &Role::Viewer)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    #[allow(non_snake_case)]
+    struct RawDeadLetter {
+        dead_letter__id: Uuid,
+        event__id: Uuid,
+        event_type__name: String,
+        subscription__id: Uuid,
+        request_attempt__id: Uuid,
+        response__id: Option<Uuid>,
+        response_error__name: Option<String>,
+        http_code: Option<i16>,
+        attempt_count: i32,
+        created_at: DateTime<Utc>,
+    }
+    let dead_letters = query_as!(
+        RawDeadLetter,
+        "
+            SELECT dl.dead_letter__id, dl.event__id, e.event_type__name, dl.subscription__id, dl.request_attempt__id, dl.response__id, r.response_error__name, r.http_code, dl.attempt_count, dl.created_at
+            FROM webhook.dead_letter AS dl
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = dl.subscription__id
+            INNER JOIN event.event AS e ON e.event__id = dl.event__id
+            LEFT JOIN webhook.response AS r ON r.response__id = dl.response__id
+            WHERE s.application__id = $1
+                AND ($2::uuid IS NULL OR dl.subscription__id = $2)
+                AND ($3::text IS NULL OR e.event_type__name = $3)
+            ORDER BY dl.created_at DESC
+            LIMIT 100
+        ",
+        &qs.application_id,
+        qs.subscription_id,
+        qs.event_type_name,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .into_iter()
+    .map(|dl| DeadLetter {
+        dead_letter_id: dl.dead_letter__id,
+        event_id: dl.event__id,
+        event_type_name: dl.event_type__name,
+        subscription_id: dl.subscription__id,
+        last_request_attempt_id: dl.request_attempt__id,
+        last_response_id: dl.response__id,
+        last_response_error_name: dl.response_error__name,
+        last_response_http_code: dl.http_code,
+        attempt_count: dl.attempt_count,
+        created_at: dl.created_at,
+    })
+    .collect();
+
+    Ok(Json(dead_letters))
+}
+
+/// Selects dead letters of an application; every other criterion is optional
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct DeadLettersSelection {
+    application_id: Uuid,
+    dead_letter_ids: Option<Vec<Uuid>>,
+    subscription_id: Option<Uuid>,
+    event_type_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct DeadLettersActionResult {
+    pub affected_count: i64,
+}
+
+#[api_v2_operation(
+    summary = "Redrive dead letters",
+    description = "Create new request attempts for the selected dead letters and remove them. Dead letters of disabled or deleted subscriptions are left untouched.",
+    operation_id = "deadLetters.redrive",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn redrive(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    body: Json<DeadLettersSelection>,
+) -> Result<Json<DeadLettersActionResult>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &body.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let res = query!(
+        "
+            WITH dl AS (
+                DELETE FROM webhook.dead_letter AS dl
+                USING webhook.subscription AS s, event.event AS e
+                WHERE s.subscription__id = dl.subscription__id AND e.event__id = dl.event__id
+                    AND s.application__id = $1
+                    AND s.is_enabled AND s.deleted_at IS NULL
+                    AND ($2::uuid[] IS NULL OR dl.dead_letter__id = ANY($2))
+                    AND ($3::uuid IS NULL OR dl.subscription__id = $3)
+                    AND ($4::text IS NULL OR e.event_type__name = $4)
+                RETURNING dl.event__id, dl.subscription__id
+            )
+            INSERT INTO webhook.request_attempt (event__id, subscription__id)
+            SELECT event__id, subscription__id
+            FROM dl
+        ",
+        &body.application_id,
+        body.dead_letter_ids.as_deref(),
+        body.subscription_id,
+        body.event_type_name,
+    )
+    .execute(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(DeadLettersActionResult {
+        affected_count: i64::try_from(res.rows_affected()).unwrap_or(i64::MAX),
+    }))
+}
+
+#[api_v2_operation(
+    summary = "Purge dead letters",
+    description = "Remove the selected dead letters without delivering their events again.",
+    operation_id = "deadLetters.purge",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn purge(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    body: Json<DeadLettersSelection>,
+) -> Result<Json<DeadLettersActionResult>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &body.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let res = query!(
+        "
+            DELETE FROM webhook.dead_letter AS dl
+            USING webhook.subscription AS s, event.event AS e
+            WHERE s.subscription__id = dl.subscription__id AND e.event__id = dl.event__id
+                AND s.application__id = $1
+                AND ($2::uuid[] IS NULL OR dl.dead_letter__id = ANY($2))
+                AND ($3::uuid IS NULL OR dl.subscription__id = $3)
+                AND ($4::text IS NULL OR e.event_type__name = $4)
+        ",
+        &body.application_id,
+        body.dead_letter_ids.as_deref(),
+        body.subscription_id,
+        body.event_type_name,
+    )
+    .execute(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(DeadLettersActionResult {
+        affected_count: i64::try_from(res.rows_affected()).unwrap_or(i64::MAX),
+    }))
+}
@@ -1,4 +1,4 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use log::error;
 use paperclip::actix::{
     api_v2_operation,
@@ -7,23 +7,86 @@ use paperclip::actix::{
 };
 use serde::{Deserialize, Serialize};
 use sqlx::{query, query_as};
+use std::str::FromStr;
+use strum::{EnumString, IntoStaticStr};
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::hook0_client::{EventEventTypeCreated, EventEventTypeRemoved, Hook0ClientEvent};
+use crate::hook0_client::{
+    EventEventTypeCreated, EventEventTypeRemoved, EventEventTypeUpdated, Hook0ClientEvent,
+};
 use crate::iam::{get_owner_organization, AuthProof, Role};
 use crate::openapi::OaApplicationSecret;
 use crate::problems::Hook0Problem;
 
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    Apiv2Schema,
+    EnumString,
+    IntoStaticStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum EventTypeStatus {
+    Active,
+    Deprecated,
+    Retired,
+}
+
 #[derive(Debug, Serialize, Apiv2Schema)]
 pub struct EventType {
     service_name: String,
     resource_type_name: String,
     verb_name: String,
-    // status
+    status: EventTypeStatus,
+    deprecated_at: Option<DateTime<Utc>>,
+    retired_at: Option<DateTime<Utc>>,
+    successor_event_type_name: Option<String>,
     event_type_name: String,
 }
 
+#[derive(Debug)]
+#[allow(non_snake_case)]
+struct EventTypeRaw {
+    service__name: String,
+    resource_type__name: String,
+    verb__name: String,
+    status: String,
+    deprecated_at: Option<DateTime<Utc>>,
+    retired_at: Option<DateTime<Utc>>,
+    successor_event_type__name: Option<String>,
+    event_type__name: String,
+}
+
+impl EventTypeRaw {
+    /// A status that cannot be parsed is reported instead of being shown as another one
+    pub fn to_event_type(&self) -> Result<EventType, Hook0Problem> {
+        let status = EventTypeStatus::from_str(&self.status).map_err(|e| {
+            error!(
+                "Could not parse status '{}' of event type {}: {e}",
+                self.status, self.event_type__name
+            );
+            Hook0Problem::InternalServerError
+        })?;
+        Ok(EventType {
+            service_name: self.service__name.clone(),
+            resource_type_name: self.resource_type__name.clone(),
+            verb_name: self.verb__name.clone(),
+            status,
+            deprecated_at: self.deprecated_at,
+            retired_at: self.retired_at,
+            successor_event_type_name: self.successor_event_type__name.clone(),
+            event_type_name: self.event_type__name.clone(),
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
 pub struct Qs {
     application_id: Uuid,
@@ -40,6 +103,14 @@ pub struct EventTypePost {
     verb: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema, Validate)]
+pub struct EventTypeStatusPut {
+    application_id: Uuid,
+    status: EventTypeStatus,
+    #[validate(non_control_character, length(min = 1, max = 200))]
+    successor_event_type_name: Option<String>,
+}
+
 #[api_v2_operation(
     summary = "Create a new event type",
     description = "",
@@ -109,11 +180,11 @@ pub async fn create(
     .map_err(Hook0Problem::from)?;
 
     let event_type = query_as!(
-            EventType,
+            EventTypeRaw,
             "
                 INSERT INTO event.event_type (application__id, service__name, resource_type__name, verb__name)
                 VALUES ($1, $2, $3, $4)
-                RETURNING service__name AS service_name, resource_type__name AS resource_type_name, verb__name AS verb_name, event_type__name AS event_type_name
+                RETURNING service__name, resource_type__name, verb__name, status, deprecated_at, retired_at, successor_event_type__name, event_type__name
             ",
             &body.application_id,
             &body.service,
@@ -122,7 +193,8 @@ pub async fn create(
         )
         .fetch_one(&mut *tx)
         .await
-        .map_err(Hook0Problem::from)?;
+        .map_err(Hook0Problem::from)?
+        .to_event_type()?;
 
     tx.commit().await.map_err(Hook0Problem::from)?;
 
@@ -173,10 +245,10 @@ pub async fn list(
         return Err(Hook0Problem::Forbidden);
     }
 
-    let event_types = query_as!(
-            EventType,
+    let event_types: Vec<EventType> = query_as!(
+            EventTypeRaw,
             "
-                SELECT service__name AS service_name, resource_type__name AS resource_type_name, verb__name AS verb_name, event_type__name AS event_type_name
+                SELECT service__name, resource_type__name, verb__name, status, deprecated_at, retired_at, successor_event_type__name, event_type__name
                 FROM event.event_type
                 WHERE application__id = $1
                 ORDER BY event_type__name ASC
@@ -185,7 +257,10 @@ pub async fn list(
         )
         .fetch_all(&state.db)
         .await
-        .map_err(Hook0Problem::from)?;
+        .map_err(Hook0Problem::from)?
+        .iter()
+        .map(|et| et.to_event_type())
+        .collect::<Result<_, _>>()?;
 
     Ok(Json(event_types))
 }
@@ -214,9 +289,9 @@ pub async fn get(
     }
 
     let event_type = query_as!(
-            EventType,
+            EventTypeRaw,
             "
-                SELECT service__name AS service_name, resource_type__name AS resource_type_name, verb__name AS verb_name, event_type__name AS event_type_name
+                SELECT service__name, resource_type__name, verb__name, status, deprecated_at, retired_at, successor_event_type__name, event_type__name
                 FROM event.event_type
                 WHERE application__id = $1 AND event_type__name = $2
             ",
@@ -228,7 +303,7 @@ pub async fn get(
         .map_err(Hook0Problem::from)?;
 
     match event_type {
-        Some(a) => Ok(Json(a)),
+        Some(a) => Ok(Json(a.to_event_type()?)),
         None => Err(Hook0Problem::NotFound),
     }
 }
@@ -258,9 +333,9 @@ pub async fn delete(
 
     let application_id = qs.application_id;
     let event_type = query_as!(
-            EventType,
+            EventTypeRaw,
             "
-                SELECT service__name AS service_name, resource_type__name AS resource_type_name, verb__name AS verb_name, event_type__name AS event_type_name
+                SELECT service__name, resource_type__name, verb__name, status, deprecated_at, retired_at, successor_event_type__name, event_type__name
                 FROM event.event_type
                 WHERE application__id = $1 AND event_type__name = $2
             ",
@@ -279,7 +354,7 @@ pub async fn delete(
                     WHERE application__id = $1 AND event_type__name = $2
                 ",
                 &application_id,
-                &a.event_type_name,
+                &a.event_type__name,
             )
             .execute(&state.db)
             .await
@@ -291,7 +366,7 @@ pub async fn delete(
                         .await
                         .unwrap_or(Uuid::nil()),
                     application_id: qs.application_id,
-                    event_type_name: a.event_type_name,
+                    event_type_name: a.event_type__name,
                 }
                 .into();
                 if let Err(e) = hook0_client
@@ -307,3 +382,226 @@ pub async fn delete(
         None => Err(Hook0Problem::NotFound),
     }
 }
+
+#[api_v2_operation(
+    summary = "Change the lifecycle status of an event type",
+    description = "Deprecated event types can still be ingested but are flagged as such; retired event types are rejected at ingestion. A successor event type can be specified to help consumers migrate.",
+    operation_id = "eventTypes.updateStatus",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn update_status(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    event_type_name: Path<String>,
+    body: Json<EventTypeStatusPut>,
+) -> Result<Json<EventType>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &body.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    let event_type_name = event_type_name.into_inner();
+    let status: &str = body.status.into();
+
+    // Active event types do not have a successor
+    let successor_event_type_name = match body.status {
+        EventTypeStatus::Active => None,
+        _ => body.successor_event_type_name.as_deref(),
+    };
+    if let Some(successor) = successor_event_type_name {
+        if successor == event_type_name {
+            return Err(Hook0Problem::EventTypeInvalidSuccessor);
+        }
+
+        #[allow(non_snake_case)]
+        struct SuccessorStatus {
+            status: String,
+        }
+        let successor_status = query_as!(
+            SuccessorStatus,
+            "
+                SELECT status
+                FROM event.event_type
+                WHERE application__id = $1 AND event_type__name = $2
+            ",
+            &body.application_id,
+            successor,
+        )
+        .fetch_optional(&state.db)
+        .await
+        .map_err(Hook0Problem::from)?;
+
+        match successor_status.and_then(|s| EventTypeStatus::from_str(&s.status).ok()) {
+            Some(EventTypeStatus::Active) | Some(EventTypeStatus::Deprecated) => (),
+            _ => return Err(Hook0Problem::EventTypeInvalidSuccessor),
+        }
+    }
+
+    let event_type = query_as!(
+            EventTypeRaw,
+            "
+                UPDATE event.event_type
+                SET status = $1,
+                    deprecated_at = CASE WHEN $1 = 'active' THEN NULL ELSE COALESCE(deprecated_at, statement_timestamp()) END,
+                    retired_at = CASE WHEN $1 = 'retired' THEN COALESCE(retired_at, statement_timestamp()) ELSE NULL END,
+                    successor_event_type__name = $2
+                WHERE application__id = $3 AND event_type__name = $4
+                RETURNING service__name, resource_type__name, verb__name, status, deprecated_at, retired_at, successor_event_type__name, event_type__name
+            ",
+            status,
+            successor_event_type_name,
+            &body.application_id,
+            &event_type_name,
+        )
+        .fetch_optional(&state.db)
+        .await
+        .map_err(Hook0Problem::from)?
+        .map(|et| et.to_event_type())
+        .transpose()?;
+
+    match event_type {
+        Some(et) => {
+            if let Some(hook0_client) = state.hook0_client.as_ref() {
+                let hook0_client_event: Hook0ClientEvent = EventEventTypeUpdated {
+                    organization_id: get_owner_organization(&state.db, &body.application_id)
+                        .await
+                        .unwrap_or(Uuid::nil()),
+                    application_id: body.application_id,
+                    event_type_name: et.event_type_name.to_owned(),
+                    status: status.to_owned(),
+                    deprecated_at: et.deprecated_at,
+                    retired_at: et.retired_at,
+                    successor_event_type_name: et.successor_event_type_name.to_owned(),
+                    updated_by: auth.user().map(|u| u.id),
+                }
+                .into();
+                if let Err(e) = hook0_client
+                    .send_event(&hook0_client_event.mk_hook0_event())
+                    .await
+                {
+                    error!("Hook0ClientError: {e}");
+                };
+            }
+
+            Ok(Json(et))
+        }
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct DeprecatedEventTypeSubscription {
+    subscription_id: Uuid,
+    subscription_description: Option<String>,
+    subscription_is_enabled: bool,
+    event_type_name: String,
+    status: EventTypeStatus,
+    deprecated_at: Option<DateTime<Utc>>,
+    retired_at: Option<DateTime<Utc>>,
+    successor_event_type_name: Option<String>,
+}
+
+#[api_v2_operation(
+    summary = "List subscriptions to deprecated event types",
+    description = "List every subscription of the application that is still subscribed to a deprecated or retired event type (by name or through an event type pattern), so that consumers can be warned.",
+    operation_id = "eventTypes.deprecatedSubscriptions",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn deprecated_subscriptions(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    qs: Query<Qs>,
+) -> Result<Json<Vec<DeprecatedEventTypeSubscription>>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &qs.application_id, &Role::Viewer)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    #[allow(non_snake_case)]
+    struct RawDeprecatedEventTypeSubscription {
+        subscription__id: Uuid,
+        description: Option<String>,
+        is_enabled: bool,
+        event_type__name: String,
+        status: String,
+        deprecated_at: Option<DateTime<Utc>>,
+        retired_at: Option<DateTime<Utc>>,
+        successor_event_type__name: Option<String>,
+    }
+    let subscriptions = query_as!(
+        RawDeprecatedEventTypeSubscription,
+        "
+            SELECT s.subscription__id, s.description, s.is_enabled, et.event_type__name, et.status, et.deprecated_at, et.retired_at, et.successor_event_type__name
+            FROM webhook.subscription AS s
+            INNER JOIN event.event_type AS et ON et.application__id = s.application__id
+            WHERE s.application__id = $1 AND s.deleted_at IS NULL AND et.status <> 'active'
+                AND (
+                    EXISTS (
+                        SELECT 1
+                        FROM webhook.subscription__event_type AS set
+                        WHERE set.subscription__id = s.subscription__id AND set.event_type__name = et.event_type__name
+                    ) OR EXISTS (
+                        SELECT 1
+                        FROM webhook.subscription__event_type_pattern AS setp
+                        WHERE setp.subscription__id = s.subscription__id
+                            AND COALESCE(setp.service__name = et.service__name, true)
+                            AND COALESCE(setp.resource_type__name = et.resource_type__name, true)
+                            AND COALESCE(setp.verb__name = et.verb__name, true)
+                    )
+                )
+            ORDER BY et.event_type__name ASC, s.created_at ASC
+        ",
+        &qs.application_id,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .into_iter()
+    .map(|s| DeprecatedEventTypeSubscription {
+        subscription_id: s.subscription__id,
+        subscription_description: s.description,
+        subscription_is_enabled: s.is_enabled,
+        event_type_name: s.event_type__name,
+        status: EventTypeStatus::from_str(&s.status).unwrap_or(EventTypeStatus::Deprecated),
+        deprecated_at: s.deprecated_at,
+        retired_at: s.retired_at,
+        successor_event_type_name: s.successor_event_type__name,
+    })
+    .collect();
+
+    Ok(Json(subscriptions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_type_status_parsing() {
+        for status in [
+            EventTypeStatus::Active,
+            EventTypeStatus::Deprecated,
+            EventTypeStatus::Retired,
+        ] {
+            let str: &str = status.into();
+            assert_eq!(EventTypeStatus::from_str(str).unwrap(), status);
+            assert_eq!(serde_json::to_value(status).unwrap(), str);
+        }
+    }
+}
@@ -1,24 +1,28 @@
 use base64::engine::general_purpose::STANDARD as Base64;
 use base64::Engine;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use ipnetwork::IpNetwork;
+use log::{error, info};
 use paperclip::actix::{
     api_v2_operation,
     web::{Data, Json, Path, Query},
-    Apiv2Schema, CreatedJson,
+    Apiv2Schema, CreatedJson, NoContent,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use sqlx::query_as;
-use std::collections::HashMap;
+use sqlx::{query, query_as};
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
 use strum::{IntoStaticStr, VariantNames};
 use uuid::Uuid;
 use validator::Validate;
 
 use crate::extractor_user_ip::UserIp;
-use crate::iam::{AuthProof, Role};
+use crate::handlers::event_types::EventTypeStatus;
+use crate::hook0_client::{EventEventReplayed, Hook0ClientEvent};
+use crate::iam::{get_owner_organization, AuthProof, Role};
 use crate::openapi::OaApplicationSecret;
+use crate::pagination::{Cursor, Paginated};
 use crate::problems::Hook0Problem;
 use crate::quotas::Quota;
 
@@ -92,6 +96,8 @@ struct EventRaw {
     metadata: Option<Value>,
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
+    deliver_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
     application_secret__token: Uuid,
     labels: Value,
 }
@@ -106,6 +112,8 @@ impl EventRaw {
             metadata: self.metadata.clone(),
             occurred_at: self.occurred_at,
             received_at: self.received_at,
+            deliver_at: self.deliver_at,
+            expires_at: self.expires_at,
             application_secret_token: self.application_secret__token,
             labels: self.labels.clone(),
         }
@@ -121,13 +129,33 @@ pub struct Event {
     metadata: Option<Value>,
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
+    deliver_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
     application_secret_token: Uuid,
     labels: Value,
 }
 
+/// Number of events per page of the events list
+const EVENTS_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
+pub struct ListQs {
+    application_id: Uuid,
+    event_type_name: Option<String>,
+    /// Must be used with `label_value`
+    label_key: Option<String>,
+    /// Must be used with `label_key`
+    label_value: Option<String>,
+    received_after: Option<DateTime<Utc>>,
+    received_before: Option<DateTime<Utc>>,
+    payload_content_type: Option<String>,
+    /// Opaque cursor taken from the `Link` header of the previous page
+    pagination_cursor: Option<String>,
+}
+
 #[api_v2_operation(
     summary = "List latest events",
-    description = "",
+    description = "Events are sorted from the most recently received. If there are more events than fit in a page, the URL of the next page is given in the `Link` header of the response.",
     operation_id = "events.list",
     consumes = "application/json",
     produces = "application/json",
@@ -137,8 +165,8 @@ pub async fn list(
     state: Data<crate::State>,
     auth: AuthProof,
     _: OaApplicationSecret,
-    qs: Query<Qs>,
-) -> Result<Json<Vec<Event>>, Hook0Problem> {
+    qs: Query<ListQs>,
+) -> Result<Paginated<Event>, Hook0Problem> {
     if auth
         .can_access_application(&state.db, &qs.application_id, &Role::Viewer)
         .await
@@ -147,23 +175,157 @@ pub async fn list(
         return Err(Hook0Problem::Forbidden);
     }
 
+    let labels_filter = match (&qs.label_key, &qs.label_value) {
+        (Some(key), Some(value)) => Some(json!({ key: value })),
+        (None, None) => None,
+        _ => return Err(Hook0Problem::EventInvalidLabelFilter),
+    };
+    let cursor = qs
+        .pagination_cursor
+        .as_deref()
+        .map(Cursor::from_str)
+        .transpose()?;
+
     let raw_events = query_as!(
             EventRaw,
             "
-                SELECT event__id, event_type__name, payload_content_type, ip, metadata, occurred_at, received_at, application_secret__token, labels
+                SELECT event__id, event_type__name, payload_content_type, ip, metadata, occurred_at, received_at, deliver_at, expires_at, application_secret__token, labels
                 FROM event.event
                 WHERE application__id = $1
-                ORDER BY received_at DESC
-                LIMIT 100
+                    AND ($2::text IS NULL OR event_type__name = $2)
+                    AND ($3::jsonb IS NULL OR labels @> $3)
+                    AND ($4::timestamptz IS NULL OR received_at >= $4)
+                    AND ($5::timestamptz IS NULL OR received_at < $5)
+                    AND ($6::text IS NULL OR payload_content_type = $6)
+                    AND ($7::timestamptz IS NULL OR (received_at, event__id) < ($7, $8))
+                ORDER BY received_at DESC, event__id DESC
+                LIMIT $9
             ",
             &qs.application_id,
+            qs.event_type_name,
+            labels_filter,
+            qs.received_after,
+            qs.received_before,
+            qs.payload_content_type,
+            cursor.map(|c| c.date),
+            cursor.map(|c| c.id),
+            i64::try_from(EVENTS_PAGE_SIZE + 1).unwrap_or(i64::MAX),
         )
         .fetch_all(&state.db)
         .await
         .map_err(Hook0Problem::from)?;
 
-    let events = raw_events.iter().map(|re| re.to_event()).collect();
-    Ok(Json(events))
+    let events = Paginated::from_items_with_extra(raw_events, EVENTS_PAGE_SIZE, |re| Cursor {
+        date: re.received_at,
+        id: re.event__id,
+    })
+    .map(|re| re.to_event());
+    Ok(events)
+}
+
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
+pub struct SearchQs {
+    application_id: Uuid,
+    /// Must be used with `label_value`
+    label_key: Option<String>,
+    /// Must be used with `label_key`
+    label_value: Option<String>,
+    /// SQL/JSON path predicate matched against JSON payloads (for example: `$.order.id == "1234"`)
+    payload_path: Option<String>,
+    /// Words searched in text payloads
+    text: Option<String>,
+    received_after: Option<DateTime<Utc>>,
+    received_before: Option<DateTime<Utc>>,
+    /// Opaque cursor taken from the `Link` header of the previous page
+    pagination_cursor: Option<String>,
+}
+
+#[api_v2_operation(
+    summary = "Search events",
+    description = "Search events by label, by a JSON path predicate on JSON payloads and/or by words in text payloads. At least one of these criteria is required. Results are paginated like the events list.",
+    operation_id = "events.search",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn search(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    qs: Query<SearchQs>,
+) -> Result<Paginated<Event>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &qs.application_id, &Role::Viewer)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let labels_filter = match (&qs.label_key, &qs.label_value) {
+        (Some(key), Some(value)) => Some(json!({ key: value })),
+        (None, None) => None,
+        _ => return Err(Hook0Problem::EventInvalidLabelFilter),
+    };
+    if labels_filter.is_none() && qs.payload_path.is_none() && qs.text.is_none() {
+        return Err(Hook0Problem::EventInvalidSearch(
+            "At least one of `label_key`/`label_value`, `payload_path` or `text` is required."
+                .to_owned(),
+        ));
+    }
+    if let Some(path) = &qs.payload_path {
+        if let Err(sqlx::Error::Database(e)) =
+            query!("SELECT $1::text::jsonpath::text AS path", path)
+                .fetch_one(&state.db)
+                .await
+        {
+            return Err(Hook0Problem::EventInvalidSearch(format!(
+                "Invalid `payload_path`: {}",
+                e.message()
+            )));
+        }
+    }
+    let cursor = qs
+        .pagination_cursor
+        .as_deref()
+        .map(Cursor::from_str)
+        .transpose()?;
+
+    let raw_events = query_as!(
+            EventRaw,
+            "
+                SELECT event__id, event_type__name, payload_content_type, ip, metadata, occurred_at, received_at, deliver_at, expires_at, application_secret__token, labels
+                FROM event.event
+                WHERE application__id = $1
+                    AND ($2::jsonb IS NULL OR labels @> $2)
+                    AND ($3::text IS NULL OR event.payload_as_jsonb(payload, payload_content_type) @@ $3::text::jsonpath)
+                    AND ($4::text IS NULL OR event.payload_as_tsvector(payload, payload_content_type) @@ plainto_tsquery('simple'::regconfig, $4))
+                    AND ($5::timestamptz IS NULL OR received_at >= $5)
+                    AND ($6::timestamptz IS NULL OR received_at < $6)
+                    AND ($7::timestamptz IS NULL OR (received_at, event__id) < ($7, $8))
+                ORDER BY received_at DESC, event__id DESC
+                LIMIT $9
+            ",
+            &qs.application_id,
+            labels_filter,
+            qs.payload_path,
+            qs.text,
+            qs.received_after,
+            qs.received_before,
+            cursor.map(|c| c.date),
+            cursor.map(|c| c.id),
+            i64::try_from(EVENTS_PAGE_SIZE + 1).unwrap_or(i64::MAX),
+        )
+        .fetch_all(&state.db)
+        .await
+        .map_err(Hook0Problem::from)?;
+
+    let events = Paginated::from_items_with_extra(raw_events, EVENTS_PAGE_SIZE, |re| Cursor {
+        date: re.received_at,
+        id: re.event__id,
+    })
+    .map(|re| re.to_event());
+    Ok(events)
 }
 
 #[derive(Debug)]
@@ -177,6 +339,8 @@ struct EventWithPayloadRaw {
     metadata: Option<Value>,
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
+    deliver_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
     application_secret__token: Uuid,
     labels: Value,
 }
@@ -192,6 +356,8 @@ impl EventWithPayloadRaw {
             metadata: self.metadata.clone(),
             occurred_at: self.occurred_at,
             received_at: self.received_at,
+            deliver_at: self.deliver_at,
+            expires_at: self.expires_at,
             application_secret_token: self.application_secret__token,
             labels: self.labels.clone(),
         }
@@ -208,6 +374,8 @@ pub struct EventWithPayload {
     metadata: Option<Value>,
     occurred_at: DateTime<Utc>,
     received_at: DateTime<Utc>,
+    deliver_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
     application_secret_token: Uuid,
     labels: Value,
 }
@@ -238,7 +406,7 @@ pub async fn get(
     let raw_event = query_as!(
             EventWithPayloadRaw,
             "
-                SELECT event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, application_secret__token, labels
+                SELECT event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, deliver_at, expires_at, application_secret__token, labels
                 FROM event.event
                 WHERE application__id = $1 AND event__id = $2
             ",
@@ -270,6 +438,8 @@ pub struct EventPost {
     occurred_at: DateTime<Utc>,
     #[validate(custom = "crate::validators::labels")]
     labels: HashMap<String, Value>,
+    deliver_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Serialize, Apiv2Schema)]
@@ -277,6 +447,16 @@ pub struct IngestedEvent {
     application_id: Uuid,
     event_id: Uuid,
     received_at: DateTime<Utc>,
+    deliver_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    event_type_deprecation: Option<EventTypeDeprecation>,
+}
+
+/// Present when an event was ingested with a deprecated event type
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct EventTypeDeprecation {
+    deprecated_at: Option<DateTime<Utc>>,
+    successor_event_type_name: Option<String>,
 }
 
 #[api_v2_operation(
@@ -367,12 +547,79 @@ pub async fn ingest(
             let content_type = PayloadContentType::from_str(&body.payload_content_type)?;
             let payload = content_type.validate_and_decode(&body.payload)?;
 
+            // Scheduled events must be delivered before they are removed by the retention policy
+            if let Some(deliver_at) = body.deliver_at {
+                let days_of_events_retention_limit = state
+                    .quotas
+                    .get_limit_for_application(
+                        &state.db,
+                        Quota::DaysOfEventsRetention,
+                        application_id,
+                    )
+                    .await?;
+                if deliver_at
+                    > Utc::now()
+                        + Duration::try_days(days_of_events_retention_limit.into())
+                            .unwrap_or(Duration::max_value())
+                {
+                    return Err(Hook0Problem::EventDeliverAtTooFar(
+                        days_of_events_retention_limit,
+                    ));
+                }
+            }
+
+            // Expired events would never be delivered
+            if let Some(expires_at) = body.expires_at {
+                if expires_at <= body.deliver_at.unwrap_or_else(Utc::now) {
+                    return Err(Hook0Problem::EventInvalidExpiresAt);
+                }
+            }
+
+            #[allow(non_snake_case)]
+            struct EventTypeLifecycle {
+                status: String,
+                deprecated_at: Option<DateTime<Utc>>,
+                successor_event_type__name: Option<String>,
+            }
+            let event_type_lifecycle = query_as!(
+                EventTypeLifecycle,
+                "
+                    SELECT status, deprecated_at, successor_event_type__name
+                    FROM event.event_type
+                    WHERE application__id = $1 AND event_type__name = $2
+                ",
+                application_id,
+                &body.event_type,
+            )
+            .fetch_optional(&state.db)
+            .await
+            .map_err(Hook0Problem::from)?;
+            let event_type_deprecation =
+                match event_type_lifecycle.map(|l| (EventTypeStatus::from_str(&l.status), l)) {
+                    Some((Ok(EventTypeStatus::Retired), _)) => {
+                        return Err(Hook0Problem::EventTypeRetired(body.event_type.to_owned()))
+                    }
+                    Some((Ok(EventTypeStatus::Deprecated), l)) => Some(EventTypeDeprecation {
+                        deprecated_at: l.deprecated_at,
+                        successor_event_type_name: l.successor_event_type__name,
+                    }),
+                    _ => None,
+                };
+
+            #[allow(non_snake_case)]
+            struct RawIngestedEvent {
+                application_id: Uuid,
+                event_id: Uuid,
+                received_at: DateTime<Utc>,
+                deliver_at: Option<DateTime<Utc>>,
+                expires_at: Option<DateTime<Utc>>,
+            }
             let event = query_as!(
-                IngestedEvent,
+                RawIngestedEvent,
                 "
-                    INSERT INTO event.event (application__id, event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, application_secret__token, labels)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, statement_timestamp(), $9, $10)
-                    RETURNING application__id AS application_id, event__id AS event_id, received_at
+                    INSERT INTO event.event (application__id, event__id, event_type__name, payload, payload_content_type, ip, metadata, occurred_at, received_at, application_secret__token, labels, deliver_at, expires_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, statement_timestamp(), $9, $10, $11, $12)
+                    RETURNING application__id AS application_id, event__id AS event_id, received_at, deliver_at, expires_at
                 ",
                 application_id,
                 &body.event_id,
@@ -384,12 +631,21 @@ pub async fn ingest(
                 &body.occurred_at,
                 secret,
                 labels,
+                body.deliver_at,
+                body.expires_at,
             )
             .fetch_one(&state.db)
             .await
             .map_err(Hook0Problem::from)?;
 
-            Ok(CreatedJson(event))
+            Ok(CreatedJson(IngestedEvent {
+                application_id: event.application_id,
+                event_id: event.event_id,
+                received_at: event.received_at,
+                deliver_at: event.deliver_at,
+                expires_at: event.expires_at,
+                event_type_deprecation,
+            }))
         } else {
             Err(Hook0Problem::TooManyEventsToday(events_per_days_limit))
         }
@@ -398,6 +654,306 @@ pub async fn ingest(
     }
 }
 
+#[api_v2_operation(
+    summary = "Cancel the scheduled delivery of an event",
+    description = "Prevent the delivery of a scheduled event (see `deliver_at`) before its delivery date. Once the delivery date has passed, the delivery cannot be cancelled anymore (retries and replays are not affected).",
+    operation_id = "events.cancelScheduledDelivery",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn cancel_scheduled_delivery(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    event_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<NoContent, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &qs.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let event_id = event_id.into_inner();
+
+    struct ScheduledEvent {
+        deliver_at: Option<DateTime<Utc>>,
+    }
+    let event = query_as!(
+        ScheduledEvent,
+        "
+            SELECT deliver_at
+            FROM event.event
+            WHERE application__id = $1 AND event__id = $2
+        ",
+        &qs.application_id,
+        &event_id,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match event {
+        Some(ScheduledEvent {
+            deliver_at: Some(deliver_at),
+        }) => {
+            let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
+
+            // If the event was not dispatched yet, the dispatcher will not create request attempts for it
+            let cancelled = query!(
+                "
+                    UPDATE event.event
+                    SET delivery_cancelled_at = COALESCE(delivery_cancelled_at, statement_timestamp())
+                    WHERE event__id = $1 AND dispatched_at IS NULL
+                ",
+                &event_id,
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?;
+
+            // Otherwise, only the attempts created by the dispatch are delayed until the delivery date
+            let deleted = query!(
+                "
+                    DELETE FROM webhook.request_attempt
+                    WHERE event__id = $1 AND delay_until = $2 AND delay_until > statement_timestamp()
+                        AND picked_at IS NULL AND succeeded_at IS NULL AND failed_at IS NULL
+                ",
+                &event_id,
+                deliver_at,
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(Hook0Problem::from)?;
+
+            tx.commit().await.map_err(Hook0Problem::from)?;
+
+            if cancelled.rows_affected() == 0
+                && deleted.rows_affected() == 0
+                && deliver_at <= Utc::now()
+            {
+                Err(Hook0Problem::EventScheduledDeliveryStarted)
+            } else {
+                Ok(NoContent)
+            }
+        }
+        Some(ScheduledEvent { deliver_at: None }) => Err(Hook0Problem::EventNotScheduled),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct EventsRedispatchPost {
+    application_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct RedispatchedEvents {
+    /// Number of events that will be dispatched again
+    redispatched_events: i64,
+}
+
+#[api_v2_operation(
+    summary = "Dispatch again events that could not be dispatched",
+    description = "Events that could not be dispatched to subscriptions after several attempts (for example because of a database error) are set aside. This makes every set-aside event of the application eligible for dispatch again.",
+    operation_id = "events.redispatch",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn redispatch(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    body: Json<EventsRedispatchPost>,
+) -> Result<Json<RedispatchedEvents>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &body.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let redispatched = query!(
+        "
+            UPDATE event.event
+            SET dispatch_failed_at = NULL, dispatch_attempt_count = 0
+            WHERE application__id = $1 AND dispatched_at IS NULL AND dispatch_failed_at IS NOT NULL
+        ",
+        &body.application_id,
+    )
+    .execute(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(RedispatchedEvents {
+        redispatched_events: i64::try_from(redispatched.rows_affected()).unwrap_or(i64::MAX),
+    }))
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct EventReplayPost {
+    application_id: Uuid,
+    /// Restrict the replay to these subscriptions (by default, every subscription that was targeted by the event); the replay is rejected if one of them cannot be replayed to (unknown, disabled or never dispatched to)
+    subscription_ids: Option<Vec<Uuid>>,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ReplayedRequestAttempt {
+    request_attempt_id: Uuid,
+    subscription_id: Uuid,
+    created_at: DateTime<Utc>,
+}
+
+#[api_v2_operation(
+    summary = "Replay an event",
+    description = "Create new request attempts for an event, so that it is sent again to the enabled subscriptions it was dispatched to.",
+    operation_id = "events.replay",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn replay(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    event_id: Path<Uuid>,
+    body: Json<EventReplayPost>,
+) -> Result<CreatedJson<Vec<ReplayedRequestAttempt>>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &body.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let event_id = event_id.into_inner();
+
+    let event = query!(
+        "
+            SELECT event__id AS event_id
+            FROM event.event
+            WHERE application__id = $1 AND event__id = $2
+        ",
+        &body.application_id,
+        &event_id,
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+    if event.is_none() {
+        return Err(Hook0Problem::NotFound);
+    }
+
+    // Requested subscriptions must all be replayable, so that callers do not get fewer replays than they asked for
+    if let Some(subscription_ids) = body.subscription_ids.as_deref() {
+        let replayable_subscription_ids = query!(
+            "
+                SELECT s.subscription__id
+                FROM webhook.subscription AS s
+                WHERE s.application__id = $2
+                    AND s.deleted_at IS NULL
+                    AND s.is_enabled
+                    AND s.subscription__id IN (SELECT ra.subscription__id FROM webhook.request_attempt AS ra WHERE ra.event__id = $1)
+                    AND s.subscription__id = ANY($3)
+            ",
+            &event_id,
+            &body.application_id,
+            subscription_ids,
+        )
+        .fetch_all(&state.db)
+        .await
+        .map_err(Hook0Problem::from)?
+        .into_iter()
+        .map(|s| s.subscription__id)
+        .collect::<HashSet<_>>();
+        let invalid_subscription_ids = subscription_ids
+            .iter()
+            .filter(|id| !replayable_subscription_ids.contains(id))
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        if !invalid_subscription_ids.is_empty() {
+            return Err(Hook0Problem::EventReplayInvalidSubscriptions(format!(
+                "These subscriptions do not exist, are disabled or never received this event: {}.",
+                invalid_subscription_ids.join(", ")
+            )));
+        }
+    }
+
+    #[allow(non_snake_case)]
+    struct RawReplayedRequestAttempt {
+        request_attempt__id: Uuid,
+        subscription__id: Uuid,
+        created_at: DateTime<Utc>,
+    }
+    let request_attempts = query_as!(
+        RawReplayedRequestAttempt,
+        "
+            INSERT INTO webhook.request_attempt (event__id, subscription__id, retry_count)
+            SELECT $1, s.subscription__id, 0
+            FROM webhook.subscription AS s
+            WHERE s.application__id = $2
+                AND s.deleted_at IS NULL
+                AND s.is_enabled
+                AND s.subscription__id IN (SELECT ra.subscription__id FROM webhook.request_attempt AS ra WHERE ra.event__id = $1)
+                AND ($3::uuid[] IS NULL OR s.subscription__id = ANY($3))
+            RETURNING request_attempt__id, subscription__id, created_at
+        ",
+        &event_id,
+        &body.application_id,
+        body.subscription_ids.as_deref(),
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .into_iter()
+    .map(|ra| ReplayedRequestAttempt {
+        request_attempt_id: ra.request_attempt__id,
+        subscription_id: ra.subscription__id,
+        created_at: ra.created_at,
+    })
+    .collect::<Vec<_>>();
+
+    let replayed_by = auth.user().map(|u| u.id);
+    info!(
+        "Event {event_id} was replayed to {} subscription(s) by {}",
+        request_attempts.len(),
+        replayed_by
+            .map(|id| format!("user {id}"))
+            .unwrap_or_else(|| "an application secret".to_owned()),
+    );
+
+    if let Some(hook0_client) = state.hook0_client.as_ref() {
+        let hook0_client_event: Hook0ClientEvent = EventEventReplayed {
+            organization_id: get_owner_organization(&state.db, &body.application_id)
+                .await
+                .unwrap_or(Uuid::nil()),
+            application_id: body.application_id,
+            event_id,
+            subscription_ids: request_attempts
+                .iter()
+                .map(|ra| ra.subscription_id)
+                .collect(),
+            replayed_by,
+            replayed_at: Utc::now(),
+        }
+        .into();
+        if let Err(e) = hook0_client
+            .send_event(&hook0_client_event.mk_hook0_event())
+            .await
+        {
+            error!("Hook0ClientError: {e}");
+        };
+    }
+
+    Ok(CreatedJson(request_attempts))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -66,24 +66,71 @@ pub async fn health(
     state: Data<crate::State>,
     qs: Query<Key>,
 ) -> Result<Json<HealthCheck>, Hook0Problem> {
-    let qs_key = qs.into_inner().key.unwrap_or_else(|| "".to_owned());
+    check_health_check_key(&state, qs.into_inner())?;
 
-    match state.health_check_key.as_deref() {
-        Some(k) => {
-            // Comparison is not done in constant time, but stakes are very low here
-            if k.is_empty() || k == qs_key {
-                let database = sqlx::query("SELECT 1").fetch_one(&state.db).await.is_ok();
-                let health_check = HealthCheck { database };
+    let database = sqlx::query("SELECT 1").fetch_one(&state.db).await.is_ok();
+    let health_check = HealthCheck { database };
 
-                if database {
-                    Ok(Json(health_check))
-                } else {
-                    Err(Hook0Problem::ServiceUnavailable(health_check))
-                }
-            } else {
-                Err(Hook0Problem::Forbidden)
-            }
-        }
-        _ => Err(Hook0Problem::NotFound),
+    if database {
+        Ok(Json(health_check))
+    } else {
+        Err(Hook0Problem::ServiceUnavailable(health_check))
+    }
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct DispatchMetrics {
+    /// Number of received events that were not dispatched yet
+    undispatched_events: i64,
+    /// Time (in milliseconds) since the oldest undispatched event was received
+    dispatch_lag_in_ms: i64,
+    /// Number of received events that could not be dispatched and were set aside
+    undispatchable_events: i64,
+}
+
+/// Get event dispatch metrics
+#[api_v2_operation(
+    summary = "Get event dispatch metrics",
+    description = "Get an object that shows how late the dispatch of events to subscriptions is. It is protected by the same key as the health check endpoint.",
+    operation_id = "instance.dispatchMetrics",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Hook0")
+)]
+pub async fn dispatch_metrics(
+    state: Data<crate::State>,
+    qs: Query<Key>,
+) -> Result<Json<DispatchMetrics>, Hook0Problem> {
+    check_health_check_key(&state, qs.into_inner())?;
+
+    let metrics = sqlx::query!(
+        r#"
+            SELECT
+                COUNT(*) FILTER (WHERE dispatch_failed_at IS NULL) AS "undispatched_events!",
+                COALESCE((EXTRACT(EPOCH FROM statement_timestamp() - MIN(received_at) FILTER (WHERE dispatch_failed_at IS NULL)) * 1000)::bigint, 0) AS "dispatch_lag_in_ms!",
+                COUNT(*) FILTER (WHERE dispatch_failed_at IS NOT NULL) AS "undispatchable_events!"
+            FROM event.event
+            WHERE dispatched_at IS NULL
+        "#
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(DispatchMetrics {
+        undispatched_events: metrics.undispatched_events,
+        dispatch_lag_in_ms: metrics.dispatch_lag_in_ms,
+        undispatchable_events: metrics.undispatchable_events,
+    }))
+}
+
+fn check_health_check_key(state: &crate::State, qs: Key) -> Result<(), Hook0Problem> {
+    let qs_key = qs.key.unwrap_or_else(|| "".to_owned());
+
+    match state.health_check_key.as_deref() {
+        // Comparison is not done in constant time, but stakes are very low here
+        Some(k) if k.is_empty() || k == qs_key => Ok(()),
+        Some(_) => Err(Hook0Problem::Forbidden),
+        None => Err(Hook0Problem::NotFound),
     }
 }
@@ -1,11 +1,14 @@
 pub mod application_secrets;
 pub mod applications;
+pub mod dead_letters;
 pub mod errors;
 pub mod event_types;
 pub mod events;
 pub mod instance;
 pub mod organizations;
+pub mod pull;
 pub mod registrations;
+pub mod replay_jobs;
 pub mod request_attempts;
 pub mod responses;
 pub mod subscriptions;
@@ -0,0 +1,564 @@
+use actix::clock::sleep;
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse};
+use base64::engine::general_purpose::STANDARD as Base64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use log::{error, info};
+use paperclip::actix::{
+    api_v2_operation,
+    web::{Data, Json, Query},
+    Apiv2Schema,
+};
+use payload_transformation::Transformation;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::openapi::OaSubscriptionSecret;
+use crate::pagination::Cursor;
+use crate::problems::Hook0Problem;
+
+const DEFAULT_FETCH_MAX_COUNT: i64 = 10;
+
+/// How long to wait before looking for new events to stream, when there were none
+const STREAM_POLLING_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long a stream can stay silent before a comment is sent to keep the connection alive
+const STREAM_KEEP_ALIVE_PERIOD: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct PullFetch {
+    subscription_id: Uuid,
+    /// Maximum number of events to return (default: 10)
+    #[validate(range(min = 1, max = 100))]
+    max_count: Option<i64>,
+}
+
+/// Event fetched from a pull subscription; it must be acknowledged before `visible_until`, or else it will be made available again
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct PulledEvent {
+    /// Identifier to use to acknowledge this event
+    pub ack_id: Uuid,
+    pub event_id: Uuid,
+    pub event_type_name: String,
+    pub occurred_at: DateTime<Utc>,
+    pub received_at: DateTime<Utc>,
+    pub labels: Value,
+    /// Base64-encoded payload (rendering of the subscription's transformation, if any)
+    pub payload: String,
+    pub payload_content_type: String,
+    pub retry_count: i16,
+    pub visible_until: DateTime<Utc>,
+}
+
+impl PulledEvent {
+    /// Position of the event in the stream of a subscription
+    fn cursor(&self) -> Cursor {
+        Cursor {
+            date: self.received_at,
+            id: self.event_id,
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+struct PullSubscription {
+    subscription__id: Uuid,
+    secret: Uuid,
+    transformation: Option<Transformation>,
+    visibility_timeout_in_s: f64,
+}
+
+#[allow(non_snake_case)]
+struct RawPulledEvent {
+    request_attempt__id: Uuid,
+    event__id: Uuid,
+    event_type__name: String,
+    occurred_at: DateTime<Utc>,
+    received_at: DateTime<Utc>,
+    labels: Value,
+    metadata: Option<Value>,
+    payload: Vec<u8>,
+    payload_content_type: String,
+    retry_count: i16,
+    visible_until: DateTime<Utc>,
+}
+
+impl PullSubscription {
+    fn to_pulled_event(&self, e: RawPulledEvent) -> PulledEvent {
+        let (payload, payload_content_type) = match &self.transformation {
+            Some(t) => (
+                t.render_to_vec(&payload_transformation::Event {
+                    id: e.event__id,
+                    event_type: &e.event_type__name,
+                    occurred_at: e.occurred_at,
+                    labels: &e.labels,
+                    metadata: e.metadata.as_ref(),
+                    payload: &e.payload,
+                    payload_content_type: &e.payload_content_type,
+                }),
+                "application/json".to_owned(),
+            ),
+            None => (e.payload, e.payload_content_type),
+        };
+        PulledEvent {
+            ack_id: e.request_attempt__id,
+            event_id: e.event__id,
+            event_type_name: e.event_type__name,
+            occurred_at: e.occurred_at,
+            received_at: e.received_at,
+            labels: e.labels,
+            payload: Base64.encode(payload),
+            payload_content_type,
+            retry_count: e.retry_count,
+            visible_until: e.visible_until,
+        }
+    }
+}
+
+async fn authenticate(
+    db: &PgPool,
+    subscription_id: &Uuid,
+    secret: &OaSubscriptionSecret,
+) -> Result<PullSubscription, Hook0Problem> {
+    let subscription = query!(
+        "
+            SELECT s.subscription__id, s.transformation, t_pull.visibility_timeout_in_s
+            FROM webhook.subscription AS s
+            INNER JOIN webhook.target_pull AS t_pull ON t_pull.target__id = s.target__id
+            WHERE s.subscription__id = $1 AND s.secret = $2 AND s.deleted_at IS NULL
+        ",
+        subscription_id,
+        &secret.0,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .ok_or(Hook0Problem::AuthInvalidSubscriptionSecret)?;
+
+    Ok(PullSubscription {
+        subscription__id: subscription.subscription__id,
+        secret: secret.0,
+        transformation: subscription
+            .transformation
+            .map(Transformation::new)
+            .transpose()
+            .map_err(|e| Hook0Problem::SubscriptionInvalidTransformation(e.to_string()))?,
+        visibility_timeout_in_s: f64::from(subscription.visibility_timeout_in_s),
+    })
+}
+
+/// Whether the subscription still exists and still has the secret it was authenticated with
+async fn is_still_authenticated(
+    db: &PgPool,
+    subscription: &PullSubscription,
+) -> Result<bool, sqlx::Error> {
+    let subscription = query!(
+        "
+            SELECT subscription__id
+            FROM webhook.subscription
+            WHERE subscription__id = $1 AND secret = $2 AND deleted_at IS NULL
+        ",
+        &subscription.subscription__id,
+        &subscription.secret,
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(subscription.is_some())
+}
+
+/// Pick the oldest events that are waiting to be delivered and hide them until their visibility timeout expires; nothing is picked while the subscription is disabled, paused or deleted, or if its secret changed
+async fn pick_events(
+    db: &PgPool,
+    subscription: &PullSubscription,
+    max_count: i64,
+    max_retries: i16,
+) -> Result<Vec<PulledEvent>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    expire_attempts(
+        &mut tx,
+        &subscription.subscription__id,
+        subscription.visibility_timeout_in_s,
+        max_retries,
+    )
+    .await?;
+
+    let mut raw_events = query_as!(
+        RawPulledEvent,
+        r#"
+            WITH picked AS (
+                SELECT ra.request_attempt__id
+                FROM webhook.request_attempt AS ra
+                INNER JOIN event.event AS e ON e.event__id = ra.event__id
+                INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+                WHERE ra.subscription__id = $1 AND ra.picked_at IS NULL AND ra.succeeded_at IS NULL AND ra.failed_at IS NULL
+                    AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp())
+                    AND s.is_enabled AND NOT s.is_paused AND s.deleted_at IS NULL AND s.secret = $4
+                ORDER BY e.received_at ASC, e.event__id ASC
+                LIMIT $2
+                FOR UPDATE OF ra
+                SKIP LOCKED
+            )
+            UPDATE webhook.request_attempt AS ra
+            SET picked_at = statement_timestamp()
+            FROM picked, event.event AS e
+            WHERE ra.request_attempt__id = picked.request_attempt__id AND e.event__id = ra.event__id
+            RETURNING ra.request_attempt__id, ra.event__id, e.event_type__name, e.occurred_at, e.received_at, e.labels, e.metadata, e.payload, e.payload_content_type, ra.retry_count, statement_timestamp() + make_interval(secs => $3) AS "visible_until!"
+        "#,
+        &subscription.subscription__id,
+        max_count,
+        subscription.visibility_timeout_in_s,
+        &subscription.secret,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    raw_events.sort_by_key(|e| (e.received_at, e.event__id));
+    Ok(raw_events
+        .into_iter()
+        .map(|e| subscription.to_pulled_event(e))
+        .collect())
+}
+
+/// Events that were picked but not acknowledged yet and come after a position in the stream of a subscription
+async fn in_flight_events(
+    db: &PgPool,
+    subscription: &PullSubscription,
+    after: &Cursor,
+) -> Result<Vec<PulledEvent>, sqlx::Error> {
+    let raw_events = query_as!(
+        RawPulledEvent,
+        r#"
+            SELECT ra.request_attempt__id, ra.event__id, e.event_type__name, e.occurred_at, e.received_at, e.labels, e.metadata, e.payload, e.payload_content_type, ra.retry_count, ra.picked_at + make_interval(secs => $2) AS "visible_until!"
+            FROM webhook.request_attempt AS ra
+            INNER JOIN event.event AS e ON e.event__id = ra.event__id
+            WHERE ra.subscription__id = $1 AND ra.succeeded_at IS NULL AND ra.failed_at IS NULL
+                AND ra.picked_at > statement_timestamp() - make_interval(secs => $2)
+                AND (e.received_at, e.event__id) > ($3, $4)
+            ORDER BY e.received_at ASC, e.event__id ASC
+        "#,
+        &subscription.subscription__id,
+        subscription.visibility_timeout_in_s,
+        &after.date,
+        &after.id,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(raw_events
+        .into_iter()
+        .map(|e| subscription.to_pulled_event(e))
+        .collect())
+}
+
+#[api_v2_operation(
+    summary = "Fetch events of a pull subscription",
+    description = "Return the oldest events that are waiting to be delivered and hide them from other fetches until their visibility timeout expires. Events that were not acknowledged in time are made available again, until the maximum number of retries is reached.",
+    operation_id = "pull.fetch",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Pull Delivery")
+)]
+pub async fn fetch(
+    state: Data<crate::State>,
+    secret: OaSubscriptionSecret,
+    body: Json<PullFetch>,
+) -> Result<Json<Vec<PulledEvent>>, Hook0Problem> {
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+    let subscription = authenticate(&state.db, &body.subscription_id, &secret).await?;
+
+    let events = pick_events(
+        &state.db,
+        &subscription,
+        body.max_count.unwrap_or(DEFAULT_FETCH_MAX_COUNT),
+        state.pull_max_retries,
+    )
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(Json(events))
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct StreamQs {
+    subscription_id: Uuid,
+}
+
+struct StreamState {
+    db: PgPool,
+    subscription: PullSubscription,
+    max_retries: i16,
+    queue: VecDeque<PulledEvent>,
+    last_sent_at: Instant,
+}
+
+/// Format an event as a Server-Sent Event message
+fn sse_message(event: &PulledEvent) -> Bytes {
+    let data = serde_json::to_string(event).expect("could not serialize pulled event into JSON");
+    Bytes::from(format!("id: {}\ndata: {data}\n\n", event.cursor().encode()))
+}
+
+#[api_v2_operation(
+    summary = "Stream events of a pull subscription",
+    description = "Server-Sent Events stream that pushes events as they are dispatched to the subscription. Streamed events follow the same rules as fetched events: they must be acknowledged before their visibility timeout expires. When reconnecting with a `Last-Event-ID` header, events that were streamed after this one and were not acknowledged yet are sent again first.",
+    operation_id = "pull.stream",
+    consumes = "application/json",
+    produces = "text/event-stream",
+    tags("Pull Delivery")
+)]
+pub async fn stream(
+    state: Data<crate::State>,
+    req: HttpRequest,
+    secret: OaSubscriptionSecret,
+    qs: Query<StreamQs>,
+) -> Result<HttpResponse, Hook0Problem> {
+    let subscription = authenticate(&state.db, &qs.subscription_id, &secret).await?;
+
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| Hook0Problem::InvalidPaginationCursor)
+                .and_then(Cursor::from_str)
+        })
+        .transpose()?;
+    let queue = match last_event_id {
+        Some(cursor) => in_flight_events(&state.db, &subscription, &cursor)
+            .await
+            .map_err(Hook0Problem::from)?
+            .into(),
+        None => VecDeque::new(),
+    };
+
+    let initial_state = StreamState {
+        db: state.db.clone(),
+        subscription,
+        max_retries: state.pull_max_retries,
+        queue,
+        last_sent_at: Instant::now(),
+    };
+    let events = stream::unfold(initial_state, |mut state| async move {
+        loop {
+            if let Some(event) = state.queue.pop_front() {
+                state.last_sent_at = Instant::now();
+                return Some((Ok::<_, actix_web::Error>(sse_message(&event)), state));
+            }
+
+            // The stream was authenticated when it was opened, so it ends if the subscription is deleted or its secret is changed
+            match is_still_authenticated(&state.db, &state.subscription).await {
+                Ok(true) => (),
+                Ok(false) => {
+                    info!(
+                        "Closing stream of subscription {}: it was deleted or its secret changed",
+                        state.subscription.subscription__id
+                    );
+                    return None;
+                }
+                Err(e) => {
+                    error!("Could not check subscription of stream: {e}");
+                    return None;
+                }
+            }
+
+            match pick_events(
+                &state.db,
+                &state.subscription,
+                DEFAULT_FETCH_MAX_COUNT,
+                state.max_retries,
+            )
+            .await
+            {
+                Ok(events) if !events.is_empty() => state.queue.extend(events),
+                Ok(_) if state.last_sent_at.elapsed() >= STREAM_KEEP_ALIVE_PERIOD => {
+                    state.last_sent_at = Instant::now();
+                    return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), state));
+                }
+                Ok(_) => sleep(STREAM_POLLING_PERIOD).await,
+                Err(e) => {
+                    error!("Could not pick events to stream: {e}");
+                    return None;
+                }
+            }
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events))
+}
+
+/// Fail fetched attempts that were not acknowledged in time (and pending attempts whose event expired), then make their events available again or give up
+async fn expire_attempts(
+    tx: &mut Transaction<'_, Postgres>,
+    subscription_id: &Uuid,
+    visibility_timeout_in_s: f64,
+    max_retries: i16,
+) -> Result<(), sqlx::Error> {
+    let expired = query!(
+        r#"
+            UPDATE webhook.request_attempt AS ra
+            SET failed_at = statement_timestamp()
+            FROM event.event AS e
+            WHERE e.event__id = ra.event__id AND ra.subscription__id = $1 AND ra.succeeded_at IS NULL AND ra.failed_at IS NULL
+                AND (ra.picked_at <= statement_timestamp() - make_interval(secs => $2) OR e.expires_at <= statement_timestamp())
+            RETURNING ra.request_attempt__id, ra.event__id, ra.retry_count, (e.expires_at IS NOT NULL AND e.expires_at <= statement_timestamp()) AS "has_expired!"
+        "#,
+        subscription_id,
+        visibility_timeout_in_s,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let (to_retry, to_give_up): (Vec<_>, Vec<_>) = expired
+        .into_iter()
+        .partition(|ra| !ra.has_expired && ra.retry_count < max_retries);
+
+    query!(
+        "
+            INSERT INTO webhook.request_attempt (event__id, subscription__id, retry_count)
+            SELECT event__id, $1, retry_count
+            FROM UNNEST($2::uuid[], $3::smallint[]) AS retries(event__id, retry_count)
+        ",
+        subscription_id,
+        &to_retry.iter().map(|ra| ra.event__id).collect::<Vec<_>>(),
+        &to_retry
+            .iter()
+            .map(|ra| ra.retry_count + 1)
+            .collect::<Vec<_>>(),
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    for ra in to_give_up {
+        query!(
+            "
+                INSERT INTO webhook.dead_letter (event__id, subscription__id, request_attempt__id, response__id, attempt_count)
+                VALUES ($1, $2, $3, NULL, (SELECT COUNT(*)::integer FROM webhook.request_attempt WHERE event__id = $1 AND subscription__id = $2))
+                ON CONFLICT (event__id, subscription__id)
+                DO UPDATE SET request_attempt__id = EXCLUDED.request_attempt__id, response__id = EXCLUDED.response__id, attempt_count = EXCLUDED.attempt_count, created_at = statement_timestamp()
+            ",
+            ra.event__id,
+            subscription_id,
+            ra.request_attempt__id,
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct PullAck {
+    subscription_id: Uuid,
+    #[validate(length(min = 1, max = 100))]
+    ack_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct PullAckResult {
+    pub acknowledged_count: i64,
+}
+
+#[api_v2_operation(
+    summary = "Acknowledge events of a pull subscription",
+    description = "Mark fetched events as delivered so that they are not made available again. Events that were already acknowledged or made available again are ignored.",
+    operation_id = "pull.ack",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Pull Delivery")
+)]
+pub async fn ack(
+    state: Data<crate::State>,
+    secret: OaSubscriptionSecret,
+    body: Json<PullAck>,
+) -> Result<Json<PullAckResult>, Hook0Problem> {
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+    let subscription = authenticate(&state.db, &body.subscription_id, &secret).await?;
+
+    let mut tx = state.db.begin().await.map_err(Hook0Problem::from)?;
+
+    let acknowledged = query!(
+        "
+            UPDATE webhook.request_attempt
+            SET succeeded_at = statement_timestamp()
+            WHERE subscription__id = $1 AND request_attempt__id = ANY($2)
+                AND picked_at IS NOT NULL AND succeeded_at IS NULL AND failed_at IS NULL
+            RETURNING event__id
+        ",
+        &subscription.subscription__id,
+        &body.ack_ids,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    // A previous delivery of these events to this subscription might have given up
+    query!(
+        "DELETE FROM webhook.dead_letter WHERE subscription__id = $1 AND event__id = ANY($2)",
+        &subscription.subscription__id,
+        &acknowledged.iter().map(|a| a.event__id).collect::<Vec<_>>(),
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    tx.commit().await.map_err(Hook0Problem::from)?;
+    Ok(Json(PullAckResult {
+        acknowledged_count: i64::try_from(acknowledged.len()).unwrap_or(i64::MAX),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    #[test]
+    fn format_sse_message() {
+        let event = PulledEvent {
+            ack_id: Uuid::nil(),
+            event_id: Uuid::nil(),
+            event_type_name: "billing.invoice.paid".to_owned(),
+            occurred_at: Utc.with_ymd_and_hms(2024, 8, 19, 10, 0, 0).unwrap(),
+            received_at: Utc.with_ymd_and_hms(2024, 8, 19, 10, 0, 1).unwrap(),
+            labels: json!({}),
+            payload: "e30=".to_owned(),
+            payload_content_type: "application/json".to_owned(),
+            retry_count: 0,
+            visible_until: Utc.with_ymd_and_hms(2024, 8, 19, 10, 0, 31).unwrap(),
+        };
+
+        let message = String::from_utf8(sse_message(&event).to_vec()).unwrap();
+        let (id, data) = message
+            .strip_prefix("id: ")
+            .and_then(|m| m.strip_suffix("\n\n"))
+            .and_then(|m| m.split_once("\ndata: "))
+            .unwrap();
+        assert_eq!(Cursor::from_str(id).unwrap(), event.cursor());
+        assert_eq!(
+            serde_json::from_str::<Value>(data).unwrap()["ack_id"],
+            json!(Uuid::nil())
+        );
+    }
+}
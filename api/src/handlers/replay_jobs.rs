@@ -0,0 +1,301 @@
+use chrono::{DateTime, Utc};
+use paperclip::actix::{
+    api_v2_operation,
+    web::{Data, Json, Path, Query},
+    Apiv2Schema, CreatedJson,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, query_as, PgPool};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::iam::{AuthProof, Role};
+use crate::openapi::OaApplicationSecret;
+use crate::problems::Hook0Problem;
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ReplayJob {
+    pub replay_job_id: Uuid,
+    pub application_id: Uuid,
+    pub subscription_id: Uuid,
+    pub event_type: Option<String>,
+    pub failed_from: DateTime<Utc>,
+    pub failed_until: DateTime<Utc>,
+    pub total_count: i32,
+    pub enqueued_count: i32,
+    pub created_at: DateTime<Utc>,
+    pub created_by: Option<Uuid>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[allow(non_snake_case)]
+struct ReplayJobRaw {
+    replay_job__id: Uuid,
+    application__id: Uuid,
+    subscription__id: Uuid,
+    event_type__name: Option<String>,
+    failed_from: DateTime<Utc>,
+    failed_until: DateTime<Utc>,
+    total_count: i32,
+    enqueued_count: i32,
+    created_at: DateTime<Utc>,
+    created_by: Option<Uuid>,
+    started_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+impl ReplayJobRaw {
+    fn to_replay_job(&self) -> ReplayJob {
+        ReplayJob {
+            replay_job_id: self.replay_job__id,
+            application_id: self.application__id,
+            subscription_id: self.subscription__id,
+            event_type: self.event_type__name.to_owned(),
+            failed_from: self.failed_from,
+            failed_until: self.failed_until,
+            total_count: self.total_count,
+            enqueued_count: self.enqueued_count,
+            created_at: self.created_at,
+            created_by: self.created_by,
+            started_at: self.started_at,
+            completed_at: self.completed_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct Qs {
+    application_id: Uuid,
+}
+
+#[api_v2_operation(
+    summary = "List replay jobs",
+    description = "",
+    operation_id = "replayJobs.list",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn list(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    qs: Query<Qs>,
+) -> Result<Json<Vec<ReplayJob>>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &qs.application_id, &Role::Viewer)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let replay_jobs = query_as!(
+        ReplayJobRaw,
+        "
+            SELECT replay_job__id, application__id, subscription__id, event_type__name, failed_from, failed_until, total_count, enqueued_count, created_at, created_by, started_at, completed_at
+            FROM webhook.replay_job
+            WHERE application__id = $1
+            ORDER BY created_at DESC
+        ",
+        &qs.application_id,
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .into_iter()
+    .map(|rj| rj.to_replay_job())
+    .collect();
+
+    Ok(Json(replay_jobs))
+}
+
+#[api_v2_operation(
+    summary = "Get a replay job",
+    description = "Can be used to follow the progress of a replay job.",
+    operation_id = "replayJobs.get",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn get(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    replay_job_id: Path<Uuid>,
+    qs: Query<Qs>,
+) -> Result<Json<ReplayJob>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &qs.application_id, &Role::Viewer)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    let replay_job = query_as!(
+        ReplayJobRaw,
+        "
+            SELECT replay_job__id, application__id, subscription__id, event_type__name, failed_from, failed_until, total_count, enqueued_count, created_at, created_by, started_at, completed_at
+            FROM webhook.replay_job
+            WHERE application__id = $1 AND replay_job__id = $2
+        ",
+        &qs.application_id,
+        &replay_job_id.into_inner(),
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    match replay_job {
+        Some(rj) => Ok(Json(rj.to_replay_job())),
+        None => Err(Hook0Problem::NotFound),
+    }
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema, Validate)]
+pub struct ReplayJobPost {
+    application_id: Uuid,
+    subscription_id: Uuid,
+    /// Only replay events of this event type
+    #[validate(non_control_character, length(min = 1, max = 200))]
+    event_type: Option<String>,
+    /// Only replay events whose last request attempt failed (and was not retried) after this date
+    failed_from: DateTime<Utc>,
+    /// Only replay events whose last request attempt failed (and was not retried) before this date
+    failed_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Apiv2Schema)]
+pub struct ReplayJobDryRun {
+    pub total_count: i32,
+}
+
+#[api_v2_operation(
+    summary = "Count events that a replay job would replay",
+    description = "Same as creating a replay job, except nothing is created nor replayed.",
+    operation_id = "replayJobs.dryRun",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn dry_run(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    body: Json<ReplayJobPost>,
+) -> Result<Json<ReplayJobDryRun>, Hook0Problem> {
+    check_replay_job_post(&state.db, &auth, &body).await?;
+
+    let total_count = count_events_to_replay(&state.db, &body).await?;
+    Ok(Json(ReplayJobDryRun { total_count }))
+}
+
+#[api_v2_operation(
+    summary = "Create a replay job",
+    description = "Replay every event whose last request attempt for a subscription failed (and was not retried) in a time range. Events are enqueued in batches by a background task; the job can be retrieved to follow its progress.",
+    operation_id = "replayJobs.create",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Events Management")
+)]
+pub async fn create(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    body: Json<ReplayJobPost>,
+) -> Result<CreatedJson<ReplayJob>, Hook0Problem> {
+    check_replay_job_post(&state.db, &auth, &body).await?;
+
+    let total_count = count_events_to_replay(&state.db, &body).await?;
+    let replay_job = query_as!(
+        ReplayJobRaw,
+        "
+            INSERT INTO webhook.replay_job (application__id, subscription__id, event_type__name, failed_from, failed_until, total_count, created_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING replay_job__id, application__id, subscription__id, event_type__name, failed_from, failed_until, total_count, enqueued_count, created_at, created_by, started_at, completed_at
+        ",
+        &body.application_id,
+        &body.subscription_id,
+        body.event_type,
+        &body.failed_from,
+        &body.failed_until,
+        total_count,
+        auth.user().map(|u| u.id),
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    Ok(CreatedJson(replay_job.to_replay_job()))
+}
+
+async fn check_replay_job_post(
+    db: &PgPool,
+    auth: &AuthProof,
+    body: &ReplayJobPost,
+) -> Result<(), Hook0Problem> {
+    if let Err(e) = body.validate() {
+        return Err(Hook0Problem::Validation(e));
+    }
+
+    if auth
+        .can_access_application(db, &body.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
+    // Request attempts that fail after the end of the range must not be picked up by the job again
+    if body.failed_from >= body.failed_until || body.failed_until > Utc::now() {
+        return Err(Hook0Problem::ReplayJobInvalidRange);
+    }
+
+    let subscription = query!(
+        "
+            SELECT subscription__id AS subscription_id
+            FROM webhook.subscription
+            WHERE application__id = $1 AND subscription__id = $2 AND deleted_at IS NULL
+        ",
+        &body.application_id,
+        &body.subscription_id,
+    )
+    .fetch_optional(db)
+    .await
+    .map_err(Hook0Problem::from)?;
+    if subscription.is_none() {
+        return Err(Hook0Problem::NotFound);
+    }
+
+    Ok(())
+}
+
+async fn count_events_to_replay(db: &PgPool, body: &ReplayJobPost) -> Result<i32, Hook0Problem> {
+    let count = query!(
+        r#"
+            SELECT COUNT(DISTINCT ra.event__id)::integer AS "count!"
+            FROM webhook.request_attempt AS ra
+            INNER JOIN event.event AS e ON e.event__id = ra.event__id
+            WHERE ra.subscription__id = $1
+                AND ra.failed_at >= $2 AND ra.failed_at < $3
+                AND ($4::text IS NULL OR e.event_type__name = $4)
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM webhook.request_attempt AS next_ra
+                    WHERE next_ra.event__id = ra.event__id AND next_ra.subscription__id = ra.subscription__id AND next_ra.created_at > ra.created_at
+                )
+        "#,
+        &body.subscription_id,
+        &body.failed_from,
+        &body.failed_until,
+        body.event_type,
+    )
+    .fetch_one(db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .count;
+
+    Ok(count)
+}
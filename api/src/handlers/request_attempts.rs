@@ -1,16 +1,19 @@
 use chrono::{DateTime, Utc};
 use paperclip::actix::{
     api_v2_operation,
-    web::{Data, Json, Query},
+    web::{Data, Json, Path, Query},
     Apiv2Schema,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::query_as;
 use std::cmp::max;
+use std::str::FromStr;
+use strum::IntoStaticStr;
 use uuid::Uuid;
 
 use crate::iam::{AuthProof, Role};
 use crate::openapi::OaApplicationSecret;
+use crate::pagination::{Cursor, Paginated};
 use crate::problems::Hook0Problem;
 
 #[derive(Debug, Serialize, Apiv2Schema)]
@@ -90,16 +93,81 @@ impl RequestAttemptStatus {
     }
 }
 
+#[allow(non_snake_case)]
+struct RawRequestAttempt {
+    request_attempt__id: Uuid,
+    event__id: Uuid,
+    subscription__id: Uuid,
+    subscription__description: Option<String>,
+    created_at: DateTime<Utc>,
+    picked_at: Option<DateTime<Utc>>,
+    failed_at: Option<DateTime<Utc>>,
+    succeeded_at: Option<DateTime<Utc>>,
+    delay_until: Option<DateTime<Utc>>,
+    response__id: Option<Uuid>,
+    retry_count: i16,
+}
+
+impl RawRequestAttempt {
+    fn to_request_attempt(&self, current_time: &DateTime<Utc>) -> RequestAttempt {
+        RequestAttempt {
+            request_attempt_id: self.request_attempt__id,
+            event_id: self.event__id,
+            subscription: SubscriptionSummary {
+                subscription_id: self.subscription__id,
+                description: self.subscription__description.clone(),
+            },
+            created_at: self.created_at,
+            picked_at: self.picked_at,
+            failed_at: self.failed_at,
+            succeeded_at: self.succeeded_at,
+            delay_until: self.delay_until,
+            response_id: self.response__id,
+            retry_count: self.retry_count,
+            status: RequestAttemptStatus::compute(
+                current_time,
+                &self.created_at,
+                &self.picked_at,
+                &self.failed_at,
+                &self.succeeded_at,
+                &self.delay_until,
+            ),
+        }
+    }
+}
+
+/// Number of request attempts per page of the request attempts list
+const REQUEST_ATTEMPTS_PAGE_SIZE: usize = 50;
+
+/// Type of a `RequestAttemptStatus`, used to filter request attempts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Apiv2Schema, IntoStaticStr)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum RequestAttemptStatusType {
+    Waiting,
+    Pending,
+    InProgress,
+    Successful,
+    Failed,
+}
+
 #[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
 pub struct Qs {
     application_id: Uuid,
     event_id: Option<Uuid>,
     subscription_id: Option<Uuid>,
+    status: Option<RequestAttemptStatusType>,
+    response_error_name: Option<String>,
+    http_code: Option<i16>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+    /// Opaque cursor taken from the `Link` header of the previous page
+    pagination_cursor: Option<String>,
 }
 
 #[api_v2_operation(
     summary = "List request attempts",
-    description = "",
+    description = "Request attempts are sorted from the most recently created. If there are more request attempts than fit in a page, the URL of the next page is given in the `Link` header of the response.",
     operation_id = "requestAttempts.read",
     consumes = "application/json",
     produces = "application/json",
@@ -110,7 +178,7 @@ pub async fn list(
     auth: AuthProof,
     _: OaApplicationSecret,
     qs: Query<Qs>,
-) -> Result<Json<Vec<RequestAttempt>>, Hook0Problem> {
+) -> Result<Paginated<RequestAttempt>, Hook0Problem> {
     if auth
         .can_access_application(&state.db, &qs.application_id, &Role::Viewer)
         .await
@@ -119,121 +187,253 @@ pub async fn list(
         return Err(Hook0Problem::Forbidden);
     }
 
+    let cursor = qs
+        .pagination_cursor
+        .as_deref()
+        .map(Cursor::from_str)
+        .transpose()?;
+    let status: Option<&'static str> = qs.status.map(|s| s.into());
+
+    // Status conditions must be kept consistent with RequestAttemptStatus::compute
+    let raw_request_attempts = query_as!(
+        RawRequestAttempt,
+        "
+            SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.response__id, ra.retry_count, s.description AS subscription__description
+            FROM webhook.request_attempt AS ra
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            LEFT JOIN webhook.response AS r ON r.response__id = ra.response__id
+            WHERE s.application__id = $1
+                AND ($2::uuid IS NULL OR ra.event__id = $2)
+                AND ($3::uuid IS NULL OR ra.subscription__id = $3)
+                AND (
+                    $4::text IS NULL
+                    OR ($4 = 'failed' AND ra.failed_at IS NOT NULL)
+                    OR ($4 = 'successful' AND ra.failed_at IS NULL AND ra.succeeded_at IS NOT NULL)
+                    OR ($4 = 'inprogress' AND ra.failed_at IS NULL AND ra.succeeded_at IS NULL AND ra.picked_at IS NOT NULL)
+                    OR ($4 = 'waiting' AND ra.failed_at IS NULL AND ra.succeeded_at IS NULL AND ra.picked_at IS NULL AND ra.delay_until > statement_timestamp())
+                    OR ($4 = 'pending' AND ra.failed_at IS NULL AND ra.succeeded_at IS NULL AND ra.picked_at IS NULL AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp()))
+                )
+                AND ($5::text IS NULL OR r.response_error__name = $5)
+                AND ($6::smallint IS NULL OR r.http_code = $6)
+                AND ($7::timestamptz IS NULL OR ra.created_at >= $7)
+                AND ($8::timestamptz IS NULL OR ra.created_at < $8)
+                AND ($9::timestamptz IS NULL OR (ra.created_at, ra.request_attempt__id) < ($9, $10))
+            ORDER BY ra.created_at DESC, ra.request_attempt__id DESC
+            LIMIT $11
+        ",
+        &qs.application_id,
+        qs.event_id,
+        qs.subscription_id,
+        status,
+        qs.response_error_name,
+        qs.http_code,
+        qs.created_after,
+        qs.created_before,
+        cursor.map(|c| c.date),
+        cursor.map(|c| c.id),
+        i64::try_from(REQUEST_ATTEMPTS_PAGE_SIZE + 1).unwrap_or(i64::MAX),
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?;
+
+    let now = Utc::now();
+    let request_attempts =
+        Paginated::from_items_with_extra(raw_request_attempts, REQUEST_ATTEMPTS_PAGE_SIZE, |ra| {
+            Cursor {
+                date: ra.created_at,
+                id: ra.request_attempt__id,
+            }
+        })
+        .map(|ra| ra.to_request_attempt(&now));
+
+    Ok(request_attempts)
+}
+
+#[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
+pub struct RetryQs {
+    application_id: Uuid,
+}
+
+#[api_v2_operation(
+    summary = "Retry a request attempt now",
+    description = "A waiting request attempt is rescheduled to be picked immediately. A failed request attempt that was not retried (because retries were exhausted) is retried once with a new request attempt. This action is rate limited per subscription.",
+    operation_id = "requestAttempts.retry",
+    consumes = "application/json",
+    produces = "application/json",
+    tags("Subscriptions Management")
+)]
+pub async fn retry(
+    state: Data<crate::State>,
+    auth: AuthProof,
+    _: OaApplicationSecret,
+    request_attempt_id: Path<Uuid>,
+    qs: Query<RetryQs>,
+) -> Result<Json<RequestAttempt>, Hook0Problem> {
+    if auth
+        .can_access_application(&state.db, &qs.application_id, &Role::Editor)
+        .await
+        .is_none()
+    {
+        return Err(Hook0Problem::Forbidden);
+    }
+
     #[allow(non_snake_case)]
-    struct RawRequestAttempt {
-        request_attempt__id: Uuid,
+    struct RetryableRequestAttempt {
         event__id: Uuid,
         subscription__id: Uuid,
-        subscription__description: Option<String>,
         created_at: DateTime<Utc>,
         picked_at: Option<DateTime<Utc>>,
         failed_at: Option<DateTime<Utc>>,
         succeeded_at: Option<DateTime<Utc>>,
         delay_until: Option<DateTime<Utc>>,
-        response__id: Option<Uuid>,
         retry_count: i16,
+        subscription_is_active: Option<bool>,
+        is_last_attempt: Option<bool>,
     }
-    let raw_request_attempts = match (&qs.event_id, &qs.subscription_id) {
-        (None, None) => {
-            query_as!(
-                RawRequestAttempt,
-                "
-                    SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.response__id, ra.retry_count, s.description AS subscription__description
-                    FROM webhook.request_attempt AS ra
-                    INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
-                    WHERE s.application__id = $1
-                    ORDER BY ra.created_at DESC
-                    LIMIT 50
-                ",
-                &qs.application_id,
-            )
-            .fetch_all(&state.db)
-            .await
-            .map_err(Hook0Problem::from)?
-        }
-        (Some(eid), None) => {
-            query_as!(
-                RawRequestAttempt,
-                "
-                    SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.response__id, ra.retry_count, s.description AS subscription__description
-                    FROM webhook.request_attempt AS ra
-                    INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
-                    WHERE s.application__id = $1 AND ra.event__id = $2
-                    ORDER BY ra.created_at DESC
-                    LIMIT 50
-                ",
-                &qs.application_id,
-                eid,
-            )
-            .fetch_all(&state.db)
-            .await
-            .map_err(Hook0Problem::from)?
-        }
-        (None, Some(sid)) => {
-            query_as!(
-                RawRequestAttempt,
-                "
-                    SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.response__id, ra.retry_count, s.description AS subscription__description
-                    FROM webhook.request_attempt AS ra
-                    INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
-                    WHERE s.application__id = $1 AND s.subscription__id = $2
-                    ORDER BY ra.created_at DESC
-                    LIMIT 50
-                ",
-                &qs.application_id,
-                sid,
-            )
-            .fetch_all(&state.db)
-            .await
-            .map_err(Hook0Problem::from)?
-        }
-        (Some(eid), Some(sid)) => {
-            query_as!(
-                RawRequestAttempt,
-                "
-                    SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.response__id, ra.retry_count, s.description AS subscription__description
-                    FROM webhook.request_attempt AS ra
-                    INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
-                    WHERE s.application__id = $1 AND ra.event__id = $2 AND s.subscription__id = $3
-                    ORDER BY ra.created_at DESC
-                    LIMIT 50
-                ",
-                &qs.application_id,
-                eid,
-                sid,
-            )
-            .fetch_all(&state.db)
-            .await
-            .map_err(Hook0Problem::from)?
-        }
+    let request_attempt = query_as!(
+        RetryableRequestAttempt,
+        "
+            SELECT ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.retry_count,
+                s.is_enabled AND s.deleted_at IS NULL AS subscription_is_active,
+                NOT EXISTS (
+                    SELECT 1
+                    FROM webhook.request_attempt AS next_ra
+                    WHERE next_ra.event__id = ra.event__id AND next_ra.subscription__id = ra.subscription__id AND next_ra.created_at > ra.created_at
+                ) AS is_last_attempt
+            FROM webhook.request_attempt AS ra
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            WHERE s.application__id = $1 AND ra.request_attempt__id = $2
+        ",
+        &qs.application_id,
+        &request_attempt_id.as_ref(),
+    )
+    .fetch_optional(&state.db)
+    .await
+    .map_err(Hook0Problem::from)?
+    .ok_or(Hook0Problem::NotFound)?;
+
+    if request_attempt.subscription_is_active != Some(true) {
+        return Err(Hook0Problem::RequestAttemptCannotBeRetried);
+    }
+
+    let now = Utc::now();
+    let status = RequestAttemptStatus::compute(
+        &now,
+        &request_attempt.created_at,
+        &request_attempt.picked_at,
+        &request_attempt.failed_at,
+        &request_attempt.succeeded_at,
+        &request_attempt.delay_until,
+    );
+    let is_given_up = matches!(status, RequestAttemptStatus::Failed { .. })
+        && request_attempt.is_last_attempt == Some(true);
+    if !matches!(status, RequestAttemptStatus::Waiting { .. }) && !is_given_up {
+        return Err(Hook0Problem::RequestAttemptCannotBeRetried);
+    }
+
+    if state
+        .manual_retry_rate_limiter
+        .check_key(&request_attempt.subscription__id)
+        .is_err()
+    {
+        return Err(Hook0Problem::TooManyManualRetries);
+    }
+
+    let retried_request_attempt = if is_given_up {
+        query_as!(
+            RawRequestAttempt,
+            "
+                WITH ra AS (
+                    INSERT INTO webhook.request_attempt (event__id, subscription__id, retry_count)
+                    VALUES ($1, $2, $3)
+                    RETURNING request_attempt__id, event__id, subscription__id, created_at, picked_at, failed_at, succeeded_at, delay_until, response__id, retry_count
+                )
+                SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.response__id, ra.retry_count, s.description AS subscription__description
+                FROM ra
+                INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            ",
+            &request_attempt.event__id,
+            &request_attempt.subscription__id,
+            request_attempt.retry_count.saturating_add(1),
+        )
+        .fetch_one(&state.db)
+        .await
+        .map_err(Hook0Problem::from)?
+    } else {
+        // The attempt might have been picked by a worker in the meantime
+        query_as!(
+            RawRequestAttempt,
+            "
+                WITH ra AS (
+                    UPDATE webhook.request_attempt
+                    SET delay_until = statement_timestamp()
+                    WHERE request_attempt__id = $1 AND picked_at IS NULL AND succeeded_at IS NULL AND failed_at IS NULL
+                    RETURNING request_attempt__id, event__id, subscription__id, created_at, picked_at, failed_at, succeeded_at, delay_until, response__id, retry_count
+                )
+                SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.picked_at, ra.failed_at, ra.succeeded_at, ra.delay_until, ra.response__id, ra.retry_count, s.description AS subscription__description
+                FROM ra
+                INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            ",
+            &request_attempt_id.as_ref(),
+        )
+        .fetch_optional(&state.db)
+        .await
+        .map_err(Hook0Problem::from)?
+        .ok_or(Hook0Problem::RequestAttemptCannotBeRetried)?
     };
 
-    let request_attempts = raw_request_attempts
-        .iter()
-        .map(|ra| RequestAttempt {
-            request_attempt_id: ra.request_attempt__id,
-            event_id: ra.event__id,
-            subscription: SubscriptionSummary {
-                subscription_id: ra.subscription__id,
-                description: ra.subscription__description.clone(),
-            },
-            created_at: ra.created_at,
-            picked_at: ra.picked_at,
-            failed_at: ra.failed_at,
-            succeeded_at: ra.succeeded_at,
-            delay_until: ra.delay_until,
-            response_id: ra.response__id,
-            retry_count: ra.retry_count,
-            status: RequestAttemptStatus::compute(
-                &Utc::now(),
-                &ra.created_at,
-                &ra.picked_at,
-                &ra.failed_at,
-                &ra.succeeded_at,
-                &ra.delay_until,
+    Ok(Json(
+        retried_request_attempt.to_request_attempt(&Utc::now()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_types_match_serialized_statuses() {
+        let now = Utc::now();
+        let statuses = [
+            (
+                RequestAttemptStatusType::Waiting,
+                RequestAttemptStatus::Waiting {
+                    since: now,
+                    until: now,
+                },
             ),
-        })
-        .collect::<Vec<_>>();
+            (
+                RequestAttemptStatusType::Pending,
+                RequestAttemptStatus::Pending { since: now },
+            ),
+            (
+                RequestAttemptStatusType::InProgress,
+                RequestAttemptStatus::InProgress { since: now },
+            ),
+            (
+                RequestAttemptStatusType::Successful,
+                RequestAttemptStatus::Successful {
+                    at: now,
+                    full_processing_ms: 0,
+                },
+            ),
+            (
+                RequestAttemptStatusType::Failed,
+                RequestAttemptStatus::Failed {
+                    at: now,
+                    full_processing_ms: 0,
+                },
+            ),
+        ];
 
-    Ok(Json(request_attempts))
+        for (status_type, status) in statuses {
+            let name: &'static str = status_type.into();
+            assert_eq!(serde_json::to_value(status).unwrap()["type"], name);
+            assert_eq!(
+                serde_json::to_value(status_type).unwrap(),
+                serde_json::Value::from(name)
+            );
+        }
+    }
 }
@@ -13,7 +13,7 @@ use std::collections::HashMap;
 use std::time::Duration;
 use uuid::Uuid;
 
-use crate::handlers::subscriptions::Target;
+use crate::handlers::subscriptions::{DeliverySchedule, LabelCondition, Target};
 
 const PERIOD_BETWEEN_EVENT_TYPES_UPSERTS_TRIES: Duration = Duration::from_secs(2);
 
@@ -30,7 +30,9 @@ pub const EVENT_TYPES: &[&str] = &[
     "api.application_secret.updated",
     "api.application_secret.removed",
     "api.event_type.created",
+    "api.event_type.updated",
     "api.event_type.removed",
+    "api.event.replayed",
     "api.subscription.created",
     "api.subscription.updated",
     "api.subscription.removed",
@@ -119,7 +121,9 @@ pub enum Hook0ClientEvent {
     ApplicationSecretUpdated(EventApplicationSecretUpdated),
     ApplicationSecretRemoved(EventApplicationSecretRemoved),
     EventTypeCreated(EventEventTypeCreated),
+    EventTypeUpdated(EventEventTypeUpdated),
     EventTypeRemoved(EventEventTypeRemoved),
+    EventReplayed(EventEventReplayed),
     SubscriptionCreated(EventSubscriptionCreated),
     SubscriptionUpdated(EventSubscriptionUpdated),
     SubscriptionRemoved(EventSubscriptionRemoved),
@@ -162,7 +166,11 @@ impl Hook0ClientEvent {
             Self::EventTypeCreated(e @ EventEventTypeCreated { created_at, .. }) => {
                 to_event(e, Some(created_at))
             }
+            Self::EventTypeUpdated(e) => to_event(e, None),
             Self::EventTypeRemoved(e) => to_event(e, None),
+            Self::EventReplayed(e @ EventEventReplayed { replayed_at, .. }) => {
+                to_event(e, Some(replayed_at))
+            }
             Self::SubscriptionCreated(e @ EventSubscriptionCreated { created_at, .. }) => {
                 to_event(e, Some(created_at))
             }
@@ -593,6 +601,47 @@ impl From<EventEventTypeCreated> for Hook0ClientEvent {
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct EventEventTypeUpdated {
+    pub organization_id: Uuid,
+    pub application_id: Uuid,
+    pub event_type_name: String,
+    pub status: String,
+    pub deprecated_at: Option<DateTime<Utc>>,
+    pub retired_at: Option<DateTime<Utc>>,
+    pub successor_event_type_name: Option<String>,
+    pub updated_by: Option<Uuid>,
+}
+
+impl Event for EventEventTypeUpdated {
+    fn event_type(&self) -> &'static str {
+        "api.event_type.updated"
+    }
+
+    fn labels(&self) -> Vec<(String, Value)> {
+        vec![
+            (
+                INSTANCE_LABEL.to_owned(),
+                Value::String(INSTANCE_VALUE.to_owned()),
+            ),
+            (
+                ORGANIZATION_LABEL.to_owned(),
+                Value::String(self.organization_id.to_string()),
+            ),
+            (
+                APPLICATION_LABEL.to_owned(),
+                to_value(self.application_id).unwrap(),
+            ),
+        ]
+    }
+}
+
+impl From<EventEventTypeUpdated> for Hook0ClientEvent {
+    fn from(e: EventEventTypeUpdated) -> Self {
+        Self::EventTypeUpdated(e)
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct EventEventTypeRemoved {
     pub organization_id: Uuid,
@@ -629,17 +678,68 @@ impl From<EventEventTypeRemoved> for Hook0ClientEvent {
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct EventEventReplayed {
+    pub organization_id: Uuid,
+    pub application_id: Uuid,
+    pub event_id: Uuid,
+    pub subscription_ids: Vec<Uuid>,
+    pub replayed_by: Option<Uuid>,
+    pub replayed_at: DateTime<Utc>,
+}
+
+impl Event for EventEventReplayed {
+    fn event_type(&self) -> &'static str {
+        "api.event.replayed"
+    }
+
+    fn labels(&self) -> Vec<(String, Value)> {
+        vec![
+            (
+                INSTANCE_LABEL.to_owned(),
+                Value::String(INSTANCE_VALUE.to_owned()),
+            ),
+            (
+                ORGANIZATION_LABEL.to_owned(),
+                Value::String(self.organization_id.to_string()),
+            ),
+            (
+                APPLICATION_LABEL.to_owned(),
+                to_value(self.application_id).unwrap(),
+            ),
+        ]
+    }
+}
+
+impl From<EventEventReplayed> for Hook0ClientEvent {
+    fn from(e: EventEventReplayed) -> Self {
+        Self::EventReplayed(e)
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct EventSubscriptionCreated {
     pub organization_id: Uuid,
     pub application_id: Uuid,
     pub subscription_id: Uuid,
     pub is_enabled: bool,
+    pub is_paused: bool,
     pub event_types: Vec<String>,
     pub description: Option<String>,
     pub metadata: HashMap<String, Value>,
     pub label_key: String,
     pub label_value: String,
+    pub label_conditions: Vec<LabelCondition>,
+    pub filter: Option<String>,
+    pub transformation: Option<Value>,
+    pub ordered_delivery: bool,
+    pub ordering_key_label: Option<String>,
+    pub batch_max_count: Option<i32>,
+    pub batch_max_size_in_bytes: Option<i32>,
+    pub batch_linger_in_ms: i32,
+    pub delivery_schedule: Option<DeliverySchedule>,
+    pub max_requests_per_second: Option<i32>,
+    pub max_in_flight_requests: Option<i32>,
     pub target: Target,
     pub created_at: DateTime<Utc>,
 }
@@ -679,11 +779,23 @@ pub struct EventSubscriptionUpdated {
     pub application_id: Uuid,
     pub subscription_id: Uuid,
     pub is_enabled: bool,
+    pub is_paused: bool,
     pub event_types: Vec<String>,
     pub description: Option<String>,
     pub metadata: HashMap<String, Value>,
     pub label_key: String,
     pub label_value: String,
+    pub label_conditions: Vec<LabelCondition>,
+    pub filter: Option<String>,
+    pub transformation: Option<Value>,
+    pub ordered_delivery: bool,
+    pub ordering_key_label: Option<String>,
+    pub batch_max_count: Option<i32>,
+    pub batch_max_size_in_bytes: Option<i32>,
+    pub batch_linger_in_ms: i32,
+    pub delivery_schedule: Option<DeliverySchedule>,
+    pub max_requests_per_second: Option<i32>,
+    pub max_in_flight_requests: Option<i32>,
     pub target: Target,
     pub created_at: DateTime<Utc>,
 }
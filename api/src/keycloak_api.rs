@@ -18,6 +18,7 @@ pub struct KeycloakApi {
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
 pub struct User {
     pub id: Uuid,
     pub username: String,
@@ -29,6 +30,7 @@ pub struct User {
 }
 
 #[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
 pub struct GroupLookup {
     pub id: Uuid,
     pub name: String,
@@ -649,8 +651,8 @@ impl KeycloakApi {
                         error!("Could not split Location header's URL into segments",);
                         Hook0Problem::InternalServerError
                     })
-                    .and_then(|segments| {
-                        segments.last().map(|s| s.to_owned()).ok_or_else(|| {
+                    .and_then(|mut segments| {
+                        segments.next_back().map(|s| s.to_owned()).ok_or_else(|| {
                             error!("Could not get last segment of Location header's URL");
                             Hook0Problem::InternalServerError
                         })
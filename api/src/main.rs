@@ -12,9 +12,11 @@ use paperclip::actix::{web, OpenApiExt};
 use reqwest::Url;
 use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 
+mod dispatcher;
 mod extractor_user_ip;
 mod handlers;
 mod hook0_client;
@@ -25,9 +27,11 @@ mod middleware_application_secret;
 mod middleware_get_user_ip;
 mod old_events_cleanup;
 mod openapi;
+mod pagination;
 mod problems;
 mod quotas;
 mod rate_limiting;
+mod replay_jobs;
 mod validators;
 
 const APP_TITLE: &str = "Hook0 API";
@@ -145,7 +149,7 @@ struct Config {
     #[clap(long, env, default_value = "10")]
     api_rate_limiting_ip_replenish_period_in_ms: u64,
 
-    /// Set to true to disable per-token API rate limiting
+    /// Set to true to disable per-token API rate limiting (pull subscriptions are rate limited per secret with the same quota)
     #[clap(long, env)]
     disable_api_rate_limiting_token: bool,
 
@@ -157,6 +161,26 @@ struct Config {
     #[clap(long, env, default_value = "100")]
     api_rate_limiting_token_replenish_period_in_ms: u64,
 
+    /// Quota of manual retries of request attempts per subscription (must be ≥ 1)
+    #[clap(long, env, default_value = "5")]
+    manual_retry_rate_limiting_burst_size: u32,
+
+    /// Duration (in millisecond) after which one manual retry per subscription is restored in the quota (must be ≥ 1)
+    #[clap(long, env, default_value = "60000")]
+    manual_retry_rate_limiting_replenish_period_in_ms: u64,
+
+    /// Quota of test events sent to each subscription (must be ≥ 1)
+    #[clap(long, env, default_value = "5")]
+    test_event_rate_limiting_burst_size: u32,
+
+    /// Duration (in millisecond) after which one test event per subscription is restored in the quota (must be ≥ 1)
+    #[clap(long, env, default_value = "10000")]
+    test_event_rate_limiting_replenish_period_in_ms: u64,
+
+    /// If set to false (default), test events cannot be sent to targets that resolve to IPs that are not globally reachable (like "127.0.0.1" for example)
+    #[clap(long, env, default_value = "false")]
+    disable_target_ip_check: bool,
+
     /// Comma-separated allowed origins for CORS
     #[clap(long, env, use_value_delimiter = true)]
     cors_allowed_origins: Vec<String>,
@@ -213,6 +237,26 @@ struct Config {
     #[clap(long, env, default_value = "false")]
     old_events_cleanup_report_and_delete: bool,
 
+    /// Duration (in second) to wait between two batches of replay jobs
+    #[clap(long, env, default_value = "5")]
+    replay_jobs_period_in_s: u64,
+
+    /// Maximum number of request attempts that replay jobs create per batch
+    #[clap(long, env, default_value = "100")]
+    replay_jobs_batch_size: u16,
+
+    /// Duration (in millisecond) to wait before looking for events to dispatch again, when there were none
+    #[clap(long, env, default_value = "500")]
+    dispatcher_period_in_ms: u64,
+
+    /// Maximum number of events that are dispatched to subscriptions at once
+    #[clap(long, env, default_value = "100")]
+    dispatcher_batch_size: u16,
+
+    /// Maximum number of times an event of a pull subscription is made available again when it was not acknowledged in time (before giving up)
+    #[clap(long, env, default_value = "30")]
+    pull_max_retries: i16,
+
     /// If true, the secured HTTP headers will be enabled
     #[clap(long, env, default_value = "true")]
     enable_security_headers: bool,
@@ -236,6 +280,10 @@ pub struct State {
     hook0_client: Option<Hook0Client>,
     quotas: quotas::Quotas,
     health_check_key: Option<String>,
+    manual_retry_rate_limiter: Arc<rate_limiting::SubscriptionRateLimiter>,
+    test_event_rate_limiter: Arc<rate_limiting::SubscriptionRateLimiter>,
+    pull_max_retries: i16,
+    delivery_config: webhook_delivery::Config,
 }
 
 #[actix_web::main]
@@ -339,6 +387,10 @@ async fn main() -> anyhow::Result<()> {
         warn!("The master API key is defined in the current configuration; THIS MAY BE A SECURITY ISSUE IN PRODUCTION");
     }
 
+    if config.disable_target_ip_check {
+        warn!("Target IP check is disabled: this allows test events to be sent to local IP addresses (for example: loopback, LAN, ...); THIS MAY BE A SECURITY ISSUE IN PRODUCTION");
+    }
+
     // Spawn task to refresh materialized views
     let refresh_db = pool.clone();
     actix_web::rt::spawn(async move {
@@ -362,6 +414,28 @@ async fn main() -> anyhow::Result<()> {
         .await;
     });
 
+    // Spawn task to dispatch events to subscriptions
+    let dispatcher_db = pool.clone();
+    actix_web::rt::spawn(async move {
+        dispatcher::periodically_dispatch_events(
+            &dispatcher_db,
+            Duration::from_millis(config.dispatcher_period_in_ms),
+            config.dispatcher_batch_size,
+        )
+        .await;
+    });
+
+    // Spawn task to process replay jobs
+    let replay_jobs_db = pool.clone();
+    actix_web::rt::spawn(async move {
+        replay_jobs::periodically_process_replay_jobs(
+            &replay_jobs_db,
+            Duration::from_secs(config.replay_jobs_period_in_s),
+            config.replay_jobs_batch_size,
+        )
+        .await;
+    });
+
     // Initialize state
     let initial_state = State {
         db: pool,
@@ -375,6 +449,20 @@ async fn main() -> anyhow::Result<()> {
         hook0_client,
         quotas,
         health_check_key: config.health_check_key,
+        manual_retry_rate_limiter: Arc::new(rate_limiting::subscription_rate_limiter(
+            config.manual_retry_rate_limiting_burst_size,
+            config.manual_retry_rate_limiting_replenish_period_in_ms,
+        )),
+        test_event_rate_limiter: Arc::new(rate_limiting::subscription_rate_limiter(
+            config.test_event_rate_limiting_burst_size,
+            config.test_event_rate_limiting_replenish_period_in_ms,
+        )),
+        pull_max_retries: config.pull_max_retries,
+        delivery_config: webhook_delivery::Config {
+            disable_target_ip_check: config.disable_target_ip_check,
+            user_agent: concat!(crate_name!(), "/", clap::crate_version!()).to_owned(),
+            broker_connections: webhook_delivery::BrokerConnections::default(),
+        },
     };
     let keycloak_oidc_public_key = config.keycloak_oidc_public_key;
     let hook0_client_api_url = config.hook0_client_api_url;
@@ -402,6 +490,7 @@ async fn main() -> anyhow::Result<()> {
                     http::header::CONTENT_TYPE,
                 ])
                 .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+                .expose_headers([http::header::LINK])
                 .max_age(3600);
 
             for origin in &config.cors_allowed_origins {
@@ -464,9 +553,16 @@ async fn main() -> anyhow::Result<()> {
                             web::resource("").route(web::get().to(handlers::instance::get)),
                         ),
                     )
-                    .service(web::scope("/health").service(
-                        web::resource("").route(web::get().to(handlers::instance::health)),
-                    ))
+                    .service(
+                        web::scope("/health")
+                            .service(
+                                web::resource("").route(web::get().to(handlers::instance::health)),
+                            )
+                            .service(
+                                web::resource("/dispatch")
+                                    .route(web::get().to(handlers::instance::dispatch_metrics)),
+                            ),
+                    )
                     .service(
                         web::scope("/errors").service(
                             web::resource("").route(web::get().to(handlers::errors::list)),
@@ -538,10 +634,17 @@ async fn main() -> anyhow::Result<()> {
                                     .route(web::get().to(handlers::event_types::list))
                                     .route(web::post().to(handlers::event_types::create)),
                             )
+                            .service(web::resource("/deprecated/subscriptions").route(
+                                web::get().to(handlers::event_types::deprecated_subscriptions),
+                            ))
                             .service(
                                 web::resource("/{event_type_name}")
                                     .route(web::get().to(handlers::event_types::get))
                                     .route(web::delete().to(handlers::event_types::delete)),
+                            )
+                            .service(
+                                web::resource("/{event_type_name}/status")
+                                    .route(web::put().to(handlers::event_types::update_status)),
                             ),
                     )
                     .service(
@@ -566,9 +669,43 @@ async fn main() -> anyhow::Result<()> {
                             .wrap(secret_auth.clone()) // Middleware order is counter intuitive: this is executed second
                             .wrap(Compat::new(jwt_auth.clone())) // Middleware order is counter intuitive: this is executed first
                             .service(web::resource("").route(web::get().to(handlers::events::list)))
+                            .service(
+                                web::resource("/search")
+                                    .route(web::get().to(handlers::events::search)),
+                            )
+                            .service(
+                                web::resource("/redispatch")
+                                    .route(web::post().to(handlers::events::redispatch)),
+                            )
                             .service(
                                 web::resource("/{event_id}")
                                     .route(web::get().to(handlers::events::get)),
+                            )
+                            .service(web::resource("/{event_id}/scheduled_delivery").route(
+                                web::delete().to(handlers::events::cancel_scheduled_delivery),
+                            ))
+                            .service(
+                                web::resource("/{event_id}/replay")
+                                    .route(web::post().to(handlers::events::replay)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/replay_jobs")
+                            .wrap(Compat::new(rate_limiters.token()))
+                            .wrap(secret_auth.clone()) // Middleware order is counter intuitive: this is executed second
+                            .wrap(Compat::new(jwt_auth.clone())) // Middleware order is counter intuitive: this is executed first
+                            .service(
+                                web::resource("")
+                                    .route(web::get().to(handlers::replay_jobs::list))
+                                    .route(web::post().to(handlers::replay_jobs::create)),
+                            )
+                            .service(
+                                web::resource("/dry_run")
+                                    .route(web::post().to(handlers::replay_jobs::dry_run)),
+                            )
+                            .service(
+                                web::resource("/{replay_job_id}")
+                                    .route(web::get().to(handlers::replay_jobs::get)),
                             ),
                     )
                     .service(
@@ -590,11 +727,40 @@ async fn main() -> anyhow::Result<()> {
                                     .route(web::get().to(handlers::subscriptions::list))
                                     .route(web::post().to(handlers::subscriptions::add)),
                             )
+                            .service(
+                                web::resource("/evaluate_filter").route(
+                                    web::post().to(handlers::subscriptions::evaluate_filter),
+                                ),
+                            )
+                            .service(web::resource("/preview_transformation").route(
+                                web::post().to(handlers::subscriptions::preview_transformation),
+                            ))
                             .service(
                                 web::resource("/{subscription_id}")
                                     .route(web::get().to(handlers::subscriptions::get))
                                     .route(web::put().to(handlers::subscriptions::update))
                                     .route(web::delete().to(handlers::subscriptions::delete)),
+                            )
+                            .service(
+                                web::resource("/{subscription_id}/test").route(
+                                    web::post().to(handlers::subscriptions::send_test_event),
+                                ),
+                            ),
+                    )
+                    .service(
+                        // Consumers of pull subscriptions authenticate with their subscription's secret
+                        web::scope("/pull")
+                            .wrap(Compat::new(rate_limiters.subscription_secret()))
+                            .service(
+                                web::resource("/fetch")
+                                    .route(web::post().to(handlers::pull::fetch)),
+                            )
+                            .service(
+                                web::resource("/ack").route(web::post().to(handlers::pull::ack)),
+                            )
+                            .service(
+                                web::resource("/stream")
+                                    .route(web::get().to(handlers::pull::stream)),
                             ),
                     )
                     .service(
@@ -605,6 +771,28 @@ async fn main() -> anyhow::Result<()> {
                             .service(
                                 web::resource("")
                                     .route(web::get().to(handlers::request_attempts::list)),
+                            )
+                            .service(
+                                web::resource("/{request_attempt_id}/retry")
+                                    .route(web::post().to(handlers::request_attempts::retry)),
+                            ),
+                    )
+                    .service(
+                        web::scope("/dead_letters")
+                            .wrap(Compat::new(rate_limiters.token()))
+                            .wrap(secret_auth.clone()) // Middleware order is counter intuitive: this is executed second
+                            .wrap(Compat::new(jwt_auth.clone())) // Middleware order is counter intuitive: this is executed first
+                            .service(
+                                web::resource("")
+                                    .route(web::get().to(handlers::dead_letters::list)),
+                            )
+                            .service(
+                                web::resource("/redrive")
+                                    .route(web::post().to(handlers::dead_letters::redrive)),
+                            )
+                            .service(
+                                web::resource("/purge")
+                                    .route(web::post().to(handlers::dead_letters::purge)),
                             ),
                     )
                     .service(
@@ -5,7 +5,9 @@ use paperclip::actix::Apiv2Security;
 use paperclip::v2::models::{DefaultApiRaw, Info, OperationProtocol};
 use reqwest::Url;
 use serde::Deserialize;
+use uuid::Uuid;
 
+use crate::problems::Hook0Problem;
 use crate::APP_TITLE;
 
 pub fn default_spec(hook0_client_api_url: &Option<Url>) -> DefaultApiRaw {
@@ -55,3 +57,32 @@ impl FromRequest for OaApplicationSecret {
         ready(Ok(Self {}))
     }
 }
+
+/// Secret of a subscription, which consumers of pull subscriptions authenticate with
+#[derive(Apiv2Security, Deserialize)]
+#[openapi(
+    apiKey,
+    alias = "subscription_secret",
+    in = "header",
+    name = "Authorization",
+    description = "Authentication using subscription secrets, for consumers of pull subscriptions (use the format `Bearer TOKEN`)"
+)]
+pub struct OaSubscriptionSecret(pub Uuid);
+
+impl FromRequest for OaSubscriptionSecret {
+    type Error = Hook0Problem;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let secret = match req.headers().get("Authorization") {
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(|str| Uuid::parse_str(str.trim_start_matches("Bearer ")).ok())
+                .map(Self)
+                .ok_or(Hook0Problem::AuthInvalidAuthorizationHeader),
+            None => Err(Hook0Problem::AuthNoAuthorizationHeader),
+        };
+        ready(secret)
+    }
+}
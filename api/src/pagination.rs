@@ -0,0 +1,166 @@
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as Base64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use paperclip::actix::web::Json;
+use paperclip::actix::OperationModifier;
+use paperclip::v2::models::{DefaultOperationRaw, DefaultSchemaRaw};
+use paperclip::v2::schema::Apiv2Schema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use url::form_urlencoded;
+use uuid::Uuid;
+
+use crate::problems::Hook0Problem;
+
+/// Name of the query string parameter that holds the pagination cursor
+pub const CURSOR_PARAMETER: &str = "pagination_cursor";
+
+const CURSOR_SEPARATOR: char = '|';
+
+/// Position of the last item of a page sorted by date then ID (both descending)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub date: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        Base64.encode(format!(
+            "{}{CURSOR_SEPARATOR}{}",
+            self.date.to_rfc3339(),
+            self.id
+        ))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Hook0Problem;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let decoded = Base64
+            .decode(s)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or(Hook0Problem::InvalidPaginationCursor)?;
+        let (date, id) = decoded
+            .split_once(CURSOR_SEPARATOR)
+            .ok_or(Hook0Problem::InvalidPaginationCursor)?;
+
+        Ok(Self {
+            date: DateTime::parse_from_rfc3339(date)
+                .map_err(|_| Hook0Problem::InvalidPaginationCursor)?
+                .with_timezone(&Utc),
+            id: Uuid::from_str(id).map_err(|_| Hook0Problem::InvalidPaginationCursor)?,
+        })
+    }
+}
+
+/// A page of items, serialized as a JSON array; the URL of the next page (if any) is sent in a `Link` header
+#[derive(Debug)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<Cursor>,
+}
+
+impl<T> Paginated<T> {
+    /// Build a page from a list of items that was fetched with one more item than the page size, so that we know if there is a next page
+    pub fn from_items_with_extra(
+        mut items: Vec<T>,
+        page_size: usize,
+        cursor_of: impl Fn(&T) -> Cursor,
+    ) -> Self {
+        let next_cursor = if items.len() > page_size {
+            items.truncate(page_size);
+            items.last().map(cursor_of)
+        } else {
+            None
+        };
+        Self { items, next_cursor }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Paginated<U> {
+        Paginated {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+fn next_page_link(req: &HttpRequest, cursor: &Cursor) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    for (key, value) in form_urlencoded::parse(req.query_string().as_bytes()) {
+        if key != CURSOR_PARAMETER {
+            query.append_pair(&key, &value);
+        }
+    }
+    query.append_pair(CURSOR_PARAMETER, &cursor.encode());
+
+    format!("<{}?{}>; rel=\"next\"", req.path(), query.finish())
+}
+
+impl<T: Serialize> Responder for Paginated<T> {
+    type Body = actix_web::body::BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let mut res = HttpResponse::Ok();
+        if let Some(link) = self
+            .next_cursor
+            .and_then(|c| HeaderValue::from_str(&next_page_link(req, &c)).ok())
+        {
+            res.insert_header((header::LINK, link));
+        }
+        res.json(self.items)
+    }
+}
+
+impl<T: Apiv2Schema> Apiv2Schema for Paginated<T> {}
+
+impl<T: Apiv2Schema> OperationModifier for Paginated<T> {
+    fn update_response(op: &mut DefaultOperationRaw) {
+        Json::<Vec<T>>::update_response(op)
+    }
+
+    fn update_definitions(map: &mut BTreeMap<String, DefaultSchemaRaw>) {
+        Json::<Vec<T>>::update_definitions(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::test::TestRequest;
+    use chrono::TimeZone;
+
+    #[test]
+    fn cursor_roundtrip() {
+        let cursor = Cursor {
+            date: Utc.with_ymd_and_hms(2024, 6, 3, 9, 21, 47).unwrap(),
+            id: Uuid::from_str("1cd43b73-a5f0-4683-9961-cbd0c28ba565").unwrap(),
+        };
+        assert_eq!(Cursor::from_str(&cursor.encode()).unwrap(), cursor);
+        assert!(Cursor::from_str("not a cursor").is_err());
+    }
+
+    #[test]
+    fn next_page_link_replaces_cursor() {
+        let cursor = Cursor {
+            date: Utc.with_ymd_and_hms(2024, 6, 3, 9, 21, 47).unwrap(),
+            id: Uuid::nil(),
+        };
+        let req = TestRequest::get()
+            .uri("/api/v1/events?application_id=abc&pagination_cursor=old")
+            .to_http_request();
+        assert_eq!(
+            next_page_link(&req, &cursor),
+            format!(
+                "</api/v1/events?application_id=abc&pagination_cursor={}>; rel=\"next\"",
+                cursor.encode()
+            )
+        );
+    }
+}
@@ -36,29 +36,49 @@ pub enum Hook0Problem {
     InvalidRole,
 
     EventTypeAlreadyExist,
+    EventTypeInvalidSuccessor,
+    EventTypeRetired(String),
 
     UnauthorizedWorkers(Vec<String>),
+    SubscriptionInvalidFilter(String),
+    SubscriptionInvalidTransformation(String),
+    SubscriptionEndpointVerificationUnsupported,
+    SubscriptionTestEventUnsupported,
+    SubscriptionTestEventEndpointNotVerified,
 
     EventAlreadyIngested,
     EventInvalidPayloadContentType,
     EventInvalidBase64Payload(String),
     EventInvalidJsonPayload(String),
+    EventDeliverAtTooFar(QuotaValue),
+    EventNotScheduled,
+    EventScheduledDeliveryStarted,
+    EventInvalidExpiresAt,
+    EventInvalidLabelFilter,
+    EventInvalidSearch(String),
+    EventReplayInvalidSubscriptions(String),
+    ReplayJobInvalidRange,
+    RequestAttemptCannotBeRetried,
+    TooManyTestEvents,
 
     // Auth errors
     AuthNoAuthorizationHeader,
     AuthInvalidAuthorizationHeader,
     AuthApplicationSecretLookupError,
     AuthInvalidApplicationSecret,
+    AuthInvalidSubscriptionSecret,
 
     // Quota errors
     TooManyMembersPerOrganization(QuotaValue),
     TooManyApplicationsPerOrganization(QuotaValue),
     TooManyEventsToday(QuotaValue),
+    TooManyManualRetries,
 
     // Generic errors
     JsonPayload(JsonPayloadProblem),
     Validation(validator::ValidationErrors),
     NotFound,
+    InvalidPaginationCursor,
     InternalServerError,
     Forbidden,
     ServiceUnavailable(HealthCheck),
@@ -207,6 +227,23 @@ impl From<Hook0Problem> for Problem {
                 validation: None,
                 status: StatusCode::CONFLICT,
             },
+            Hook0Problem::EventTypeInvalidSuccessor => Problem {
+                id: Hook0Problem::EventTypeInvalidSuccessor,
+                title: "Invalid successor event type",
+                detail: "The successor event type must be another existing event type of the same application that is not retired.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::EventTypeRetired(event_type) => {
+                let detail = format!("Event type '{event_type}' was retired and cannot be used to ingest events anymore.");
+                Problem {
+                    id: Hook0Problem::EventTypeRetired(event_type),
+                    title: "This event type is retired",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::GONE,
+                }
+            },
 
             Hook0Problem::UnauthorizedWorkers(w) => {
                 let detail = format!("You do not have access to the following workers: {}", w.join(", "));
@@ -256,6 +293,107 @@ impl From<Hook0Problem> for Problem {
                     status: StatusCode::BAD_REQUEST,
                 }
             },
+            Hook0Problem::EventDeliverAtTooFar(limit) => {
+                let detail = format!("Events cannot be scheduled to be delivered more than {limit} days in the future because they would be removed by the retention policy first.");
+                Problem {
+                    id: Hook0Problem::EventDeliverAtTooFar(limit),
+                    title: "Event delivery is scheduled too far in the future",
+                    detail: detail.into(),
+                    validation: None,
+                    status: StatusCode::BAD_REQUEST,
+                }
+            },
+            Hook0Problem::EventNotScheduled => Problem {
+                id: Hook0Problem::EventNotScheduled,
+                title: "Event is not scheduled",
+                detail: "This event was not ingested with a `deliver_at` date, so its delivery cannot be cancelled.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+            Hook0Problem::EventScheduledDeliveryStarted => Problem {
+                id: Hook0Problem::EventScheduledDeliveryStarted,
+                title: "Scheduled delivery already started",
+                detail: "The delivery date of this event has passed, so its delivery cannot be cancelled anymore.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+            Hook0Problem::RequestAttemptCannotBeRetried => Problem {
+                id: Hook0Problem::RequestAttemptCannotBeRetried,
+                title: "Request attempt cannot be retried",
+                detail: "Only waiting request attempts and failed request attempts that were not retried (for an enabled subscription) can be retried manually.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+            Hook0Problem::SubscriptionInvalidFilter(detail) => Problem {
+                id: Hook0Problem::SubscriptionInvalidFilter(detail.to_owned()),
+                title: "Invalid subscription filter",
+                detail: detail.into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::SubscriptionInvalidTransformation(detail) => Problem {
+                id: Hook0Problem::SubscriptionInvalidTransformation(detail.to_owned()),
+                title: "Invalid subscription transformation",
+                detail: detail.into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::SubscriptionEndpointVerificationUnsupported => Problem {
+                id: Hook0Problem::SubscriptionEndpointVerificationUnsupported,
+                title: "Endpoint verification is not supported by this target",
+                detail: "Only subscriptions with an HTTP target can have their endpoint verified.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::SubscriptionTestEventUnsupported => Problem {
+                id: Hook0Problem::SubscriptionTestEventUnsupported,
+                title: "Test events cannot be sent to this target",
+                detail: "Events of pull subscriptions are fetched by consumers, so they cannot be sent.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::SubscriptionTestEventEndpointNotVerified => Problem {
+                id: Hook0Problem::SubscriptionTestEventEndpointNotVerified,
+                title: "Stored events cannot be sent to an endpoint that is not verified",
+                detail: "The endpoint of this subscription has not passed verification, so only synthetic test events can be sent to it.".into(),
+                validation: None,
+                status: StatusCode::CONFLICT,
+            },
+            Hook0Problem::ReplayJobInvalidRange => Problem {
+                id: Hook0Problem::ReplayJobInvalidRange,
+                title: "Invalid replay job time range",
+                detail: "`failed_from` must be before `failed_until`, which must not be in the future.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::EventInvalidLabelFilter => Problem {
+                id: Hook0Problem::EventInvalidLabelFilter,
+                title: "Invalid label filter",
+                detail: "`label_key` and `label_value` must be used together.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::EventInvalidSearch(detail) => Problem {
+                id: Hook0Problem::EventInvalidSearch(detail.to_owned()),
+                title: "Invalid event search",
+                detail: detail.into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
+            Hook0Problem::EventReplayInvalidSubscriptions(detail) => Problem {
+                id: Hook0Problem::EventReplayInvalidSubscriptions(detail.to_owned()),
+                title: "Event cannot be replayed to some subscriptions",
+                detail: detail.into(),
+                validation: None,
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+            },
+            Hook0Problem::EventInvalidExpiresAt => Problem {
+                id: Hook0Problem::EventInvalidExpiresAt,
+                title: "Invalid event expiration date",
+                detail: "`expires_at` must be in the future and after `deliver_at` (if specified).".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
 
             // Auth error
             Hook0Problem::AuthNoAuthorizationHeader => Problem {
@@ -286,6 +424,13 @@ impl From<Hook0Problem> for Problem {
                 validation: None,
                 status: StatusCode::FORBIDDEN,
             },
+            Hook0Problem::AuthInvalidSubscriptionSecret => Problem {
+                id: Hook0Problem::AuthInvalidSubscriptionSecret,
+                title: "Invalid subscription secret",
+                detail: "The provided secret does not match the secret of a subscription that has a pull target.".into(),
+                validation: None,
+                status: StatusCode::FORBIDDEN,
+            },
 
             // Quota errors
             Hook0Problem::TooManyMembersPerOrganization(limit) => {
@@ -318,6 +463,21 @@ impl From<Hook0Problem> for Problem {
                     status: StatusCode::TOO_MANY_REQUESTS,
                 }
             },
+            Hook0Problem::TooManyManualRetries => Problem {
+                id: Hook0Problem::TooManyManualRetries,
+                title: "Too many manual retries for this subscription",
+                detail: "Request attempts of this subscription were retried manually too many times recently. Please wait a moment before trying again.".into(),
+                validation: None,
+                status: StatusCode::TOO_MANY_REQUESTS,
+            },
+
+            Hook0Problem::TooManyTestEvents => Problem {
+                id: Hook0Problem::TooManyTestEvents,
+                title: "Too many test events for this subscription",
+                detail: "Too many test events were sent to this subscription recently. Please wait a moment before trying again.".into(),
+                validation: None,
+                status: StatusCode::TOO_MANY_REQUESTS,
+            },
 
             // Generic errors
             Hook0Problem::JsonPayload(e) => {
@@ -354,6 +514,13 @@ impl From<Hook0Problem> for Problem {
                 validation: None,
                 status: StatusCode::NOT_FOUND,
             },
+            Hook0Problem::InvalidPaginationCursor => Problem {
+                id: Hook0Problem::InvalidPaginationCursor,
+                title: "Invalid pagination cursor",
+                detail: "The pagination cursor could not be decoded. Use the URL provided in the `Link` header of the previous page.".into(),
+                validation: None,
+                status: StatusCode::BAD_REQUEST,
+            },
             Hook0Problem::Forbidden => Problem {
                 id: Hook0Problem::Forbidden,
                 title: "Insufficient rights",
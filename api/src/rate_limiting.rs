@@ -2,13 +2,17 @@ use actix_governor::{
     GlobalKeyExtractor, Governor, GovernorConfig, GovernorConfigBuilder, KeyExtractor,
 };
 use actix_web::middleware::Condition;
-use actix_web::HttpMessage;
+use actix_web::{FromRequest, HttpMessage};
 use governor::middleware::NoOpMiddleware;
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
 use ipnetwork::IpNetwork;
 use log::warn;
+use std::num::NonZeroU32;
+use std::time::Duration;
 use uuid::Uuid;
 
 use crate::iam::{AuthProof, Hook0Claims};
+use crate::openapi::OaSubscriptionSecret;
 use crate::problems::Hook0Problem;
 
 #[derive(Debug, Clone)]
@@ -20,6 +24,7 @@ pub struct Hook0RateLimiters {
     global: GovernorConfig<GlobalKeyExtractor, NoOpMiddleware>,
     ip: GovernorConfig<UserIpKeyExtractor, NoOpMiddleware>,
     token: GovernorConfig<TokenKeyExtractor, NoOpMiddleware>,
+    subscription_secret: GovernorConfig<SubscriptionSecretKeyExtractor, NoOpMiddleware>,
 }
 
 impl Hook0RateLimiters {
@@ -54,6 +59,12 @@ impl Hook0RateLimiters {
             .per_millisecond(api_rate_limiting_token_replenish_period_in_ms)
             .finish()
             .expect("Could not build per-token rate limiter; check configuration");
+        let subscription_secret = GovernorConfigBuilder::default()
+            .key_extractor(SubscriptionSecretKeyExtractor)
+            .burst_size(api_rate_limiting_token_burst_size)
+            .per_millisecond(api_rate_limiting_token_replenish_period_in_ms)
+            .finish()
+            .expect("Could not build per-subscription-secret rate limiter; check configuration");
 
         if disable_api_rate_limiting {
             warn!("API rate limiting is disabled");
@@ -77,6 +88,7 @@ impl Hook0RateLimiters {
             global,
             ip,
             token,
+            subscription_secret,
         }
     }
 
@@ -100,6 +112,32 @@ impl Hook0RateLimiters {
             Governor::new(&self.token),
         )
     }
+
+    /// Per-token rate limiter for consumers of pull subscriptions, which authenticate with their subscription's secret
+    pub fn subscription_secret(
+        &self,
+    ) -> Condition<Governor<SubscriptionSecretKeyExtractor, NoOpMiddleware>> {
+        Condition::new(
+            !self.disable_api_rate_limiting && !self.disable_api_rate_limiting_token,
+            Governor::new(&self.subscription_secret),
+        )
+    }
+}
+
+/// Rate limiter for actions that trigger deliveries to a subscription
+pub type SubscriptionRateLimiter = DefaultKeyedRateLimiter<Uuid>;
+
+pub fn subscription_rate_limiter(
+    burst_size: u32,
+    replenish_period_in_ms: u64,
+) -> SubscriptionRateLimiter {
+    let quota = Quota::with_period(Duration::from_millis(replenish_period_in_ms))
+        .expect("Could not build per-subscription rate limiter; check configuration")
+        .allow_burst(
+            NonZeroU32::new(burst_size)
+                .expect("Could not build per-subscription rate limiter; check configuration"),
+        );
+    RateLimiter::keyed(quota)
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -170,3 +208,80 @@ impl KeyExtractor for TokenKeyExtractor {
         })
     }
 }
+
+#[derive(Debug, Clone, Copy)]
+pub struct SubscriptionSecretKeyExtractor;
+
+impl KeyExtractor for SubscriptionSecretKeyExtractor {
+    type Key = Uuid;
+    type KeyExtractionError = Hook0Problem;
+
+    fn name(&self) -> &'static str {
+        "subscription secret"
+    }
+
+    fn extract(
+        &self,
+        req: &actix_web::dev::ServiceRequest,
+    ) -> Result<Self::Key, Self::KeyExtractionError> {
+        OaSubscriptionSecret::extract(req.request())
+            .into_inner()
+            .map(|secret| secret.0)
+    }
+
+    // Secrets must not end up in logs
+    fn key_name(&self, _key: &Self::Key) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use actix_web::http::StatusCode;
+    use actix_web::middleware::Compat;
+    use actix_web::{test, web, App, HttpResponse};
+
+    fn rate_limiters() -> Hook0RateLimiters {
+        Hook0RateLimiters::new(false, false, 100, 100, false, 100, 100, false, 2, 60_000)
+    }
+
+    #[actix_web::test]
+    async fn subscription_secret_rate_limiting() {
+        let app = test::init_service(
+            App::new().service(
+                web::scope("/pull")
+                    .wrap(Compat::new(rate_limiters().subscription_secret()))
+                    .route("/fetch", web::post().to(HttpResponse::Ok)),
+            ),
+        )
+        .await;
+        let call = |secret: Option<Uuid>| {
+            let req = test::TestRequest::post().uri("/pull/fetch");
+            match secret {
+                Some(secret) => req.insert_header(("Authorization", format!("Bearer {secret}"))),
+                None => req,
+            }
+            .to_request()
+        };
+
+        let secret = Uuid::new_v4();
+        for _ in 0..2 {
+            let res = test::call_service(&app, call(Some(secret))).await;
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+        let res = test::call_service(&app, call(Some(secret))).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Quotas are per secret
+        let res = test::call_service(&app, call(Some(Uuid::new_v4()))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let err = test::try_call_service(&app, call(None)).await.unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use actix::clock::sleep;
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, trace};
+use sqlx::{query, query_as, PgPool};
+use std::time::Duration;
+use uuid::Uuid;
+
+const STARTUP_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+pub async fn periodically_process_replay_jobs(db: &PgPool, period: Duration, batch_size: u16) {
+    sleep(STARTUP_GRACE_PERIOD).await;
+
+    loop {
+        if let Err(e) = process_next_replay_job_batch(db, batch_size).await {
+            error!("Could not process replay jobs: {e}");
+        }
+
+        // Waiting between batches throttles replay jobs so that they do not flood workers
+        sleep(period).await;
+    }
+}
+
+/// Enqueue the next batch of request attempts of the oldest unfinished replay job
+async fn process_next_replay_job_batch(db: &PgPool, batch_size: u16) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    #[allow(non_snake_case)]
+    struct PendingReplayJob {
+        replay_job__id: Uuid,
+        subscription__id: Uuid,
+        event_type__name: Option<String>,
+        failed_from: DateTime<Utc>,
+        failed_until: DateTime<Utc>,
+        remaining_count: i32,
+    }
+    let job = query_as!(
+        PendingReplayJob,
+        r#"
+            SELECT replay_job__id, subscription__id, event_type__name, failed_from, failed_until, total_count - enqueued_count AS "remaining_count!"
+            FROM webhook.replay_job
+            WHERE completed_at IS NULL
+            ORDER BY created_at ASC
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        "#
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let job = match job {
+        Some(j) => j,
+        None => {
+            trace!("No replay job to process");
+            tx.commit().await?;
+            return Ok(());
+        }
+    };
+
+    debug!("Processing replay job {}", job.replay_job__id);
+    let limit = job.remaining_count.clamp(0, i32::from(batch_size));
+
+    // Replayed events no longer match the filter because they now have a newer request attempt; nothing is enqueued (which completes the job) once the subscription is disabled or deleted
+    let enqueued = query!(
+        "
+            INSERT INTO webhook.request_attempt (event__id, subscription__id, retry_count)
+            SELECT ra.event__id, ra.subscription__id, 0::smallint
+            FROM webhook.request_attempt AS ra
+            INNER JOIN event.event AS e ON e.event__id = ra.event__id
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            WHERE ra.subscription__id = $1 AND s.is_enabled AND s.deleted_at IS NULL
+                AND ra.failed_at >= $2 AND ra.failed_at < $3
+                AND ($4::text IS NULL OR e.event_type__name = $4)
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM webhook.request_attempt AS next_ra
+                    WHERE next_ra.event__id = ra.event__id AND next_ra.subscription__id = ra.subscription__id AND next_ra.created_at > ra.created_at
+                )
+            LIMIT $5
+        ",
+        &job.subscription__id,
+        &job.failed_from,
+        &job.failed_until,
+        job.event_type__name,
+        i64::from(limit),
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+    let enqueued = i32::try_from(enqueued).unwrap_or(i32::MAX);
+
+    let completed = enqueued < limit || enqueued >= job.remaining_count;
+    query!(
+        "
+            UPDATE webhook.replay_job
+            SET enqueued_count = enqueued_count + $1,
+                started_at = COALESCE(started_at, statement_timestamp()),
+                completed_at = CASE WHEN $2 THEN statement_timestamp() ELSE NULL END
+            WHERE replay_job__id = $3
+        ",
+        enqueued,
+        completed,
+        &job.replay_job__id,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    if completed {
+        info!(
+            "Replay job {} is completed ({enqueued} request attempts enqueued in last batch)",
+            job.replay_job__id
+        );
+    } else {
+        debug!(
+            "Replay job {}: {enqueued} request attempts enqueued",
+            job.replay_job__id
+        );
+    }
+    Ok(())
+}
@@ -21,6 +21,10 @@ const CODE_LABELS_PROPERTY_TYPE: &str = "labels-property-type";
 const CODE_LABELS_PROPERTY_LENGTH: &str = "labels-property-length";
 const CODE_EVENT_TYPES_SIZE: &str = "event-types-size";
 const CODE_EVENT_TYPES_NAME_LENGTH: &str = "event-types-name-length";
+const CODE_EVENT_TYPES_PATTERN: &str = "event-types-pattern";
+
+/// Wildcard that matches any service, resource type or verb in an event type pattern
+pub const EVENT_TYPE_PATTERN_WILDCARD: &str = "*";
 
 fn json_type(val: &Value) -> &'static str {
     match val {
@@ -152,14 +156,28 @@ pub fn event_types(val: &[String]) -> Result<(), ValidationError> {
     }
 
     let mut invalid_names = vec![];
+    let mut invalid_patterns = vec![];
 
     for (index, name) in val.iter().enumerate() {
         if !(EVENT_TYPES_NAME_MIN_LENGTH..=EVENT_TYPES_NAME_MAX_LENGTH).contains(&name.len()) {
             invalid_names.push(index);
+        } else if is_event_type_pattern(name) && !is_valid_event_type_pattern(name) {
+            invalid_patterns.push(index);
         }
     }
 
-    if !invalid_names.is_empty() {
+    if !invalid_patterns.is_empty() {
+        let invalid = invalid_patterns
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(ValidationError {
+                code: CODE_EVENT_TYPES_PATTERN.into(),
+                message: Some(format!("Event type patterns must be made of a service, a resource type and a verb separated by dots, each of them being either a name or '{EVENT_TYPE_PATTERN_WILDCARD}' (invalid patterns were spotted at the following indexes: {invalid})").into()),
+                params: HashMap::new(),
+            })
+    } else if !invalid_names.is_empty() {
         let invalid = invalid_names
             .iter()
             .map(|i| i.to_string())
@@ -175,6 +193,20 @@ pub fn event_types(val: &[String]) -> Result<(), ValidationError> {
     }
 }
 
+/// Event types containing a wildcard are patterns that match every event type sharing their other parts (e.g. `billing.*.*`)
+pub fn is_event_type_pattern(name: &str) -> bool {
+    name.contains(EVENT_TYPE_PATTERN_WILDCARD)
+}
+
+fn is_valid_event_type_pattern(pattern: &str) -> bool {
+    let parts = pattern.split('.').collect::<Vec<_>>();
+    parts.len() == 3
+        && parts.iter().all(|part| {
+            *part == EVENT_TYPE_PATTERN_WILDCARD
+                || (!part.is_empty() && !is_event_type_pattern(part))
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -434,4 +466,24 @@ mod tests {
             CODE_EVENT_TYPES_NAME_LENGTH
         );
     }
+
+    #[test]
+    fn event_types_patterns() {
+        let val = [
+            "billing.*.*",
+            "*.invoice.*",
+            "*.*.*",
+            "billing.invoice.paid",
+        ]
+        .map(|et| et.to_owned());
+        assert!(event_types(&val).is_ok());
+
+        for invalid in ["billing.*", "billing.inv*.*", "*.*.*.*", "billing..*"] {
+            let output = event_types(&[invalid.to_owned()]);
+            assert_eq!(
+                output.err().map(|e| e.code).unwrap_or_else(|| "".into()),
+                CODE_EVENT_TYPES_PATTERN
+            );
+        }
+    }
 }
@@ -1,16 +1,13 @@
 mod monitoring;
-mod work;
 
 use chrono::{DateTime, Utc};
 use clap::{crate_name, crate_version, Parser};
 use log::{debug, error, info, trace, warn};
-use reqwest::header::HeaderMap;
 use reqwest::Url;
 use sqlx::postgres::types::PgInterval;
 use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use sqlx::{query, query_as, PgConnection, PgPool};
 use std::cmp::min;
-use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::str::FromStr;
 use std::time::Duration;
@@ -19,8 +16,10 @@ use tokio::sync::mpsc::{channel, Sender};
 use tokio::task::JoinSet;
 use tokio::time::sleep;
 use uuid::Uuid;
-
-use work::*;
+use webhook_delivery::{
+    batch_body, verify_endpoint, work, work_batch, EndpointVerificationError, RequestAttempt,
+    ResponseError, Target,
+};
 
 #[derive(Debug, Clone, Parser)]
 #[clap(author, about, version)]
@@ -61,11 +60,21 @@ struct Config {
     #[clap(long, env, default_value = "60")]
     monitoring_heartbeat_min_period_in_s: u64,
 
-    /// If set to false (default), webhooks that target IPs that are not globally reachable (like "127.0.0.1" for example) will fail
+    /// If set to false (default), webhooks that target IPs that are not globally reachable (like "127.0.0.1" for example) will fail, and so will every Kafka target (the addresses of the brokers advertised by a Kafka cluster cannot be checked)
     #[clap(long, env, default_value = "false")]
     disable_target_ip_check: bool,
 }
 
+impl Config {
+    fn delivery_config(&self) -> webhook_delivery::Config {
+        webhook_delivery::Config {
+            disable_target_ip_check: self.disable_target_ip_check,
+            user_agent: concat!(crate_name!(), "/", crate_version!()).to_owned(),
+            broker_connections: webhook_delivery::BrokerConnections::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum WorkerType {
     Public { worker_id: Option<Uuid> },
@@ -84,35 +93,18 @@ impl std::fmt::Display for WorkerType {
     }
 }
 
-#[derive(Debug, Clone)]
-#[allow(non_snake_case)]
-pub struct RequestAttempt {
-    pub request_attempt__id: Uuid,
-    pub event__id: Uuid,
-    pub subscription__id: Uuid,
-    pub created_at: DateTime<Utc>,
-    pub retry_count: i16,
-    pub http_method: String,
-    pub http_url: String,
-    pub http_headers: serde_json::Value,
-    pub event_type__name: String,
-    pub payload: Vec<u8>,
-    pub payload_content_type: String,
-    pub secret: Uuid,
-}
-
-impl RequestAttempt {
-    /// Parse headers of HTTP target from JSON and prepare them to be fed to reqwest
-    fn headers(&self) -> anyhow::Result<HeaderMap> {
-        let hashmap = serde_json::from_value::<HashMap<String, String>>(self.http_headers.clone())?;
-        let headermap = HeaderMap::try_from(&hashmap)?;
-        Ok(headermap)
-    }
-}
-
 /// How long to wait when there are no unprocessed items to pick
 const POLLING_SLEEP: Duration = Duration::from_secs(1);
 
+/// How long to wait when there are no endpoints to verify
+const ENDPOINT_VERIFICATION_POLLING_SLEEP: Duration = Duration::from_secs(5);
+
+/// How long to wait before calling an endpoint again after a transient verification error (it must be longer than the HTTP timeout, so that an endpoint that is being called is not picked again)
+const ENDPOINT_VERIFICATION_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Number of times an endpoint is called before its verification fails because of transient errors
+const ENDPOINT_VERIFICATION_MAX_ATTEMPTS: i32 = 3;
+
 /// How long to wait before first fast retry
 const MINIMUM_FAST_RETRY_DELAY: Duration = Duration::from_secs(5);
 
@@ -142,8 +134,9 @@ async fn main() -> anyhow::Result<()> {
     );
 
     debug!("Connecting to database...");
+    // Units might need a second connection while their transaction is open (to count requests sent to throughput-limited subscriptions); endpoint verification needs one more
     let pool = PgPoolOptions::new()
-        .max_connections(config.concurrent.into())
+        .max_connections(u32::from(config.concurrent) * 2 + 1)
         .connect_with(
             PgConnectOptions::from_str(&config.database_url)?
                 .application_name(&format!("{}-{worker_version}-{worker_name}", crate_name!(),)),
@@ -177,6 +170,7 @@ async fn main() -> anyhow::Result<()> {
 
     let mut tasks = JoinSet::new();
     let expected_tasks_len = usize::from(config.concurrent)
+        + 1
         + if config.monitoring_heartbeat_url.is_some() {
             1
         } else {
@@ -206,15 +200,35 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
+    // Connections to brokers are shared by all units
+    let delivery_config = config.delivery_config();
+
+    {
+        let p = pool.clone();
+        let delivery_config = delivery_config.clone();
+        tasks.spawn(async move {
+            loop {
+                let t = verify_endpoints(&delivery_config, &p, &worker_type).await;
+                if let Err(ref e) = t {
+                    error!("Endpoint verification task crashed: {e}");
+                }
+                sleep(Duration::from_secs(1)).await;
+                info!("Restarting endpoint verification task...");
+            }
+        });
+    }
+
     for unit_id in 0..config.concurrent {
         let p = pool.clone();
         let wn = worker_name.to_owned();
         let wv = worker_version.to_owned();
         let tx = heartbeat_tx.to_owned();
         let cfg = config.to_owned();
+        let dc = delivery_config.clone();
         tasks.spawn(async move {
             loop {
-                let t = look_for_work(&cfg, unit_id, &p, &wn, &wv, &worker_type, tx.clone()).await;
+                let t =
+                    look_for_work(&cfg, &dc, unit_id, &p, &wn, &wv, &worker_type, tx.clone()).await;
                 if let Err(ref e) = t {
                     error!("Unit {unit_id} crashed: {e}");
                 }
@@ -232,6 +246,7 @@ async fn main() -> anyhow::Result<()> {
 #[allow(clippy::too_many_arguments)]
 async fn look_for_work(
     config: &Config,
+    delivery_config: &webhook_delivery::Config,
     unit_id: u8,
     pool: &PgPool,
     worker_name: &str,
@@ -250,16 +265,42 @@ async fn look_for_work(
                 query_as!(
                     RequestAttempt,
                     "
-                        SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.retry_count, t_http.method AS http_method, t_http.url AS http_url, t_http.headers AS http_headers, e.event_type__name, e.payload AS payload, e.payload_content_type AS payload_content_type, s.secret
+                        WITH in_flight AS MATERIALIZED (
+                            SELECT (((l.classid::bigint << 32) | l.objid::bigint) / 1000) AS throughput_lock_id, COUNT(*) AS request_count
+                            FROM pg_locks AS l
+                            WHERE l.locktype = 'advisory' AND l.objsubid = 1 AND l.granted AND l.database = (SELECT oid FROM pg_database WHERE datname = current_database())
+                            GROUP BY 1
+                        )
+                        SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.retry_count, t.target AS \"target!\", e.event_type__name, e.payload AS payload, e.payload_content_type AS payload_content_type, e.occurred_at, e.labels, e.metadata, s.secret, s.transformation, s.batch_max_count, s.batch_max_size_in_bytes, e.expires_at, s.delivery_schedule, s.max_requests_per_second, s.max_in_flight_requests
                         FROM webhook.request_attempt AS ra
                         INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
                         LEFT JOIN webhook.subscription__worker AS sw ON sw.subscription__id = s.subscription__id
                         INNER JOIN event.application AS a ON a.application__id = s.application__id
                         INNER JOIN iam.organization AS o ON o.organization__id = a.organization__id
                         LEFT JOIN iam.organization__worker AS ow ON ow.organization__id = o.organization__id AND ow.default = true
-                        INNER JOIN webhook.target_http AS t_http ON t_http.target__id = s.target__id
+                        INNER JOIN webhook.target_definition AS t ON t.target__id = s.target__id AND t.target_type <> 'pull'
                         INNER JOIN event.event AS e ON e.event__id = ra.event__id
-                        WHERE ra.succeeded_at IS NULL AND ra.failed_at IS NULL AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp()) AND (COALESCE(sw.worker__id, ow.worker__id) IS NULL OR COALESCE(sw.worker__id, ow.worker__id) = $1)
+                        LEFT JOIN in_flight AS inf ON inf.throughput_lock_id = s.throughput_lock_id
+                        WHERE ra.succeeded_at IS NULL AND ra.failed_at IS NULL AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp()) AND NOT s.is_paused AND (COALESCE(sw.worker__id, ow.worker__id) IS NULL OR COALESCE(sw.worker__id, ow.worker__id) = $1)
+                            AND (NOT s.ordered_delivery OR NOT EXISTS (
+                                SELECT 1
+                                FROM webhook.request_attempt AS prev_ra
+                                INNER JOIN event.event AS prev_e ON prev_e.event__id = prev_ra.event__id
+                                WHERE prev_ra.subscription__id = ra.subscription__id AND prev_ra.succeeded_at IS NULL AND prev_ra.failed_at IS NULL
+                                    AND (prev_e.received_at, prev_e.event__id) < (e.received_at, e.event__id)
+                                    AND (s.ordering_key_label IS NULL OR prev_e.labels ->> s.ordering_key_label IS NOT DISTINCT FROM e.labels ->> s.ordering_key_label)
+                            ))
+                            AND (s.batch_max_count IS NULL OR ra.created_at <= statement_timestamp() - make_interval(secs => s.batch_linger_in_ms / 1000.0) OR (
+                                SELECT COUNT(*)
+                                FROM webhook.request_attempt AS batch_ra
+                                WHERE batch_ra.subscription__id = ra.subscription__id AND batch_ra.succeeded_at IS NULL AND batch_ra.failed_at IS NULL AND (batch_ra.delay_until IS NULL OR batch_ra.delay_until <= statement_timestamp())
+                            ) >= s.batch_max_count)
+                            AND (s.max_in_flight_requests IS NULL OR COALESCE(inf.request_count, 0) < s.max_in_flight_requests)
+                            AND (s.max_requests_per_second IS NULL OR NOT EXISTS (
+                                SELECT 1
+                                FROM webhook.subscription__delivery_rate AS dr
+                                WHERE dr.subscription__id = s.subscription__id AND dr.window_started_at = date_trunc('second', statement_timestamp()) AND dr.request_count >= s.max_requests_per_second
+                            ))
                         ORDER BY ra.created_at ASC
                         LIMIT 1
                         FOR UPDATE OF ra
@@ -275,16 +316,42 @@ async fn look_for_work(
                 query_as!(
                     RequestAttempt,
                     "
-                        SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.retry_count, t_http.method AS http_method, t_http.url AS http_url, t_http.headers AS http_headers, e.event_type__name, e.payload AS payload, e.payload_content_type AS payload_content_type, s.secret
+                        WITH in_flight AS MATERIALIZED (
+                            SELECT (((l.classid::bigint << 32) | l.objid::bigint) / 1000) AS throughput_lock_id, COUNT(*) AS request_count
+                            FROM pg_locks AS l
+                            WHERE l.locktype = 'advisory' AND l.objsubid = 1 AND l.granted AND l.database = (SELECT oid FROM pg_database WHERE datname = current_database())
+                            GROUP BY 1
+                        )
+                        SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.retry_count, t.target AS \"target!\", e.event_type__name, e.payload AS payload, e.payload_content_type AS payload_content_type, e.occurred_at, e.labels, e.metadata, s.secret, s.transformation, s.batch_max_count, s.batch_max_size_in_bytes, e.expires_at, s.delivery_schedule, s.max_requests_per_second, s.max_in_flight_requests
                         FROM webhook.request_attempt AS ra
                         INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
                         LEFT JOIN webhook.subscription__worker AS sw ON sw.subscription__id = s.subscription__id
                         INNER JOIN event.application AS a ON a.application__id = s.application__id
                         INNER JOIN iam.organization AS o ON o.organization__id = a.organization__id
                         LEFT JOIN iam.organization__worker AS ow ON ow.organization__id = o.organization__id AND ow.default = true
-                        INNER JOIN webhook.target_http AS t_http ON t_http.target__id = s.target__id
+                        INNER JOIN webhook.target_definition AS t ON t.target__id = s.target__id AND t.target_type <> 'pull'
                         INNER JOIN event.event AS e ON e.event__id = ra.event__id
-                        WHERE ra.succeeded_at IS NULL AND ra.failed_at IS NULL AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp()) AND (COALESCE(sw.worker__id, ow.worker__id) = $1)
+                        LEFT JOIN in_flight AS inf ON inf.throughput_lock_id = s.throughput_lock_id
+                        WHERE ra.succeeded_at IS NULL AND ra.failed_at IS NULL AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp()) AND NOT s.is_paused AND (COALESCE(sw.worker__id, ow.worker__id) = $1)
+                            AND (NOT s.ordered_delivery OR NOT EXISTS (
+                                SELECT 1
+                                FROM webhook.request_attempt AS prev_ra
+                                INNER JOIN event.event AS prev_e ON prev_e.event__id = prev_ra.event__id
+                                WHERE prev_ra.subscription__id = ra.subscription__id AND prev_ra.succeeded_at IS NULL AND prev_ra.failed_at IS NULL
+                                    AND (prev_e.received_at, prev_e.event__id) < (e.received_at, e.event__id)
+                                    AND (s.ordering_key_label IS NULL OR prev_e.labels ->> s.ordering_key_label IS NOT DISTINCT FROM e.labels ->> s.ordering_key_label)
+                            ))
+                            AND (s.batch_max_count IS NULL OR ra.created_at <= statement_timestamp() - make_interval(secs => s.batch_linger_in_ms / 1000.0) OR (
+                                SELECT COUNT(*)
+                                FROM webhook.request_attempt AS batch_ra
+                                WHERE batch_ra.subscription__id = ra.subscription__id AND batch_ra.succeeded_at IS NULL AND batch_ra.failed_at IS NULL AND (batch_ra.delay_until IS NULL OR batch_ra.delay_until <= statement_timestamp())
+                            ) >= s.batch_max_count)
+                            AND (s.max_in_flight_requests IS NULL OR COALESCE(inf.request_count, 0) < s.max_in_flight_requests)
+                            AND (s.max_requests_per_second IS NULL OR NOT EXISTS (
+                                SELECT 1
+                                FROM webhook.subscription__delivery_rate AS dr
+                                WHERE dr.subscription__id = s.subscription__id AND dr.window_started_at = date_trunc('second', statement_timestamp()) AND dr.request_count >= s.max_requests_per_second
+                            ))
                         ORDER BY ra.created_at ASC
                         LIMIT 1
                         FOR UPDATE OF ra
@@ -298,42 +365,83 @@ async fn look_for_work(
         };
 
         if let Some(attempt) = next_attempt {
+            // Subscriptions with a delivery schedule only receive requests while it is open
+            if let Some(delay_until) = attempt.deferred_until(Utc::now()) {
+                debug!(
+                    "[unit={unit_id}] Deferring request attempt {} until {delay_until} because of the delivery schedule of its subscription",
+                    attempt.request_attempt__id
+                );
+                query!(
+                    "
+                        UPDATE webhook.request_attempt
+                        SET delay_until = $1
+                        WHERE request_attempt__id = $2
+                    ",
+                    delay_until,
+                    attempt.request_attempt__id,
+                )
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+                continue;
+            }
+
+            // Throughput-limited subscriptions keep their attempts pending while they are saturated
+            if !acquire_throughput_slot(&mut tx, pool, &attempt).await? {
+                debug!(
+                    "[unit={unit_id}] Leaving request attempt {} pending because its subscription reached its throughput limit",
+                    attempt.request_attempt__id
+                );
+                tx.rollback().await?;
+                continue;
+            }
+
+            // Batched subscriptions send other pending attempts along with the picked one
+            let mut attempts = vec![attempt];
+            let batch_body = if let Some(batch_max_count) = attempts[0].batch_max_count {
+                if !attempts[0].has_expired() {
+                    let mates =
+                        fetch_batch_mates(&mut tx, &attempts[0], batch_max_count - 1).await?;
+                    attempts.extend(mates);
+                }
+                Some(batch_body(&mut attempts))
+            } else {
+                None
+            };
+            let attempt_ids = attempts
+                .iter()
+                .map(|a| a.request_attempt__id)
+                .collect::<Vec<_>>();
+
             // Set picked_at
-            debug!(
-                "[unit={unit_id}] Picking request attempt {}",
-                &attempt.request_attempt__id
-            );
+            debug!("[unit={unit_id}] Picking request attempts {attempt_ids:?}");
             query!(
                 "
                     UPDATE webhook.request_attempt
                     SET picked_at = statement_timestamp(), worker_name = $1, worker_version = $2
-                    WHERE request_attempt__id = $3
+                    WHERE request_attempt__id = ANY($3)
                 ",
                 &worker_name,
                 &worker_version,
-                attempt.request_attempt__id
+                &attempt_ids,
             )
             .execute(&mut *tx)
             .await?;
-            info!(
-                "[unit={unit_id}] Picked request attempt {}",
-                &attempt.request_attempt__id
-            );
+            info!("[unit={unit_id}] Picked request attempts {attempt_ids:?}");
 
             // Work
-            let response = work(config, &attempt).await;
+            let response = match batch_body {
+                Some(body) => work_batch(delivery_config, &attempts, body).await,
+                None => work(delivery_config, &attempts[0]).await,
+            };
             debug!(
-                "[unit={unit_id}] Got a response for request attempt {} in {} ms",
-                &attempt.request_attempt__id,
+                "[unit={unit_id}] Got a response for request attempts {attempt_ids:?} in {} ms",
                 &response.elapsed_time_ms()
             );
             trace!("[unit={unit_id}] {:?}", &response);
 
             // Store response
-            debug!(
-                "[unit={unit_id}] Storing response for request attempt {}",
-                &attempt.request_attempt__id
-            );
+            debug!("[unit={unit_id}] Storing response for request attempts {attempt_ids:?}");
             let response_id = query!(
                 "
                     INSERT INTO webhook.response (response_error__name, http_code, headers, body, elapsed_time_ms)
@@ -350,87 +458,114 @@ async fn look_for_work(
             .await?
             .response__id;
 
-            // Associate response and request attempt
-            debug!(
-                "[unit={unit_id}] Associating response {} with request attempt {}",
-                &response_id, &attempt.request_attempt__id
-            );
-            #[allow(clippy::suspicious_else_formatting)] // Clippy false positive
-            query!(
-                "UPDATE webhook.request_attempt SET response__id = $1 WHERE request_attempt__id = $2",
-                response_id, attempt.request_attempt__id
-            )
-            .execute(&mut *tx)
-            .await?;
-
-            if response.is_success() {
-                // Mark attempt as completed
+            for attempt in &attempts {
+                // Associate response and request attempt
                 debug!(
-                    "[unit={unit_id}] Completing request attempt {}",
-                    &attempt.request_attempt__id
+                    "[unit={unit_id}] Associating response {} with request attempt {}",
+                    &response_id, &attempt.request_attempt__id
                 );
+                #[allow(clippy::suspicious_else_formatting)] // Clippy false positive
                 query!(
-                    "UPDATE webhook.request_attempt SET succeeded_at = statement_timestamp() WHERE request_attempt__id = $1",
-                    attempt.request_attempt__id
+                    "UPDATE webhook.request_attempt SET response__id = $1 WHERE request_attempt__id = $2",
+                    response_id, attempt.request_attempt__id
                 )
                 .execute(&mut *tx)
                 .await?;
 
-                info!(
-                    "[unit={unit_id}] Request attempt {} was completed sucessfully",
-                    &attempt.request_attempt__id
-                );
-            } else {
-                // Mark attempt as failed
-                debug!(
-                    "[unit={unit_id}] Failing request attempt {}",
-                    &attempt.request_attempt__id
-                );
-                query!(
-                    "UPDATE webhook.request_attempt SET failed_at = statement_timestamp() WHERE request_attempt__id = $1",
-                    attempt.request_attempt__id
-                )
-                .execute(&mut *tx)
-                .await?;
+                if response.is_success() {
+                    // Mark attempt as completed
+                    debug!(
+                        "[unit={unit_id}] Completing request attempt {}",
+                        &attempt.request_attempt__id
+                    );
+                    query!(
+                        "UPDATE webhook.request_attempt SET succeeded_at = statement_timestamp() WHERE request_attempt__id = $1",
+                        attempt.request_attempt__id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
 
-                // Creating a retry request or giving up
-                if let Some(retry_in) = compute_next_retry(
-                    &mut tx,
-                    &attempt.subscription__id,
-                    config.max_fast_retries,
-                    config.max_slow_retries,
-                    attempt.retry_count,
-                )
-                .await?
-                {
-                    let next_retry_count = attempt.retry_count + 1;
-                    let retry_id = query!(
-                        "
-                            INSERT INTO webhook.request_attempt (event__id, subscription__id, delay_until, retry_count)
-                            VALUES ($1, $2, statement_timestamp() + $3, $4)
-                            RETURNING request_attempt__id
-                        ",
+                    // A previous delivery of this event to this subscription might have given up
+                    query!(
+                        "DELETE FROM webhook.dead_letter WHERE event__id = $1 AND subscription__id = $2",
                         attempt.event__id,
                         attempt.subscription__id,
-                        PgInterval::try_from(retry_in).unwrap(),
-                        next_retry_count,
                     )
-                    .fetch_one(&mut *tx)
-                    .await?
-                    .request_attempt__id;
+                    .execute(&mut *tx)
+                    .await?;
 
                     info!(
-                        "[unit={unit_id}] Request attempt {} failed; retry #{} created as {} to be picked in {}s",
-                        &attempt.request_attempt__id,
-                        &next_retry_count,
-                        &retry_id,
-                        &retry_in.as_secs()
+                        "[unit={unit_id}] Request attempt {} was completed sucessfully",
+                        &attempt.request_attempt__id
                     );
                 } else {
-                    info!(
-                        "[unit={unit_id}] Request attempt {} failed after {} attempts; giving up",
-                        &attempt.request_attempt__id, &attempt.retry_count,
+                    // Mark attempt as failed
+                    debug!(
+                        "[unit={unit_id}] Failing request attempt {}",
+                        &attempt.request_attempt__id
                     );
+                    query!(
+                        "UPDATE webhook.request_attempt SET failed_at = statement_timestamp() WHERE request_attempt__id = $1",
+                        attempt.request_attempt__id
+                    )
+                    .execute(&mut *tx)
+                    .await?;
+
+                    // Creating a retry request or giving up
+                    if let Some(retry_in) = compute_next_retry(
+                        &mut tx,
+                        &attempt.subscription__id,
+                        attempt.expires_at,
+                        config.max_fast_retries,
+                        config.max_slow_retries,
+                        attempt.retry_count,
+                    )
+                    .await?
+                    {
+                        let next_retry_count = attempt.retry_count + 1;
+                        let retry_id = query!(
+                            "
+                                INSERT INTO webhook.request_attempt (event__id, subscription__id, delay_until, retry_count)
+                                VALUES ($1, $2, statement_timestamp() + $3, $4)
+                                RETURNING request_attempt__id
+                            ",
+                            attempt.event__id,
+                            attempt.subscription__id,
+                            PgInterval::try_from(retry_in).unwrap(),
+                            next_retry_count,
+                        )
+                        .fetch_one(&mut *tx)
+                        .await?
+                        .request_attempt__id;
+
+                        info!(
+                            "[unit={unit_id}] Request attempt {} failed; retry #{} created as {} to be picked in {}s",
+                            &attempt.request_attempt__id,
+                            &next_retry_count,
+                            &retry_id,
+                            &retry_in.as_secs()
+                        );
+                    } else {
+                        query!(
+                            "
+                                INSERT INTO webhook.dead_letter (event__id, subscription__id, request_attempt__id, response__id, attempt_count)
+                                VALUES ($1, $2, $3, $4, (SELECT COUNT(*)::integer FROM webhook.request_attempt WHERE event__id = $1 AND subscription__id = $2))
+                                ON CONFLICT (event__id, subscription__id)
+                                DO UPDATE SET request_attempt__id = EXCLUDED.request_attempt__id, response__id = EXCLUDED.response__id, attempt_count = EXCLUDED.attempt_count, created_at = statement_timestamp()
+                            ",
+                            attempt.event__id,
+                            attempt.subscription__id,
+                            attempt.request_attempt__id,
+                            response_id,
+                        )
+                        .execute(&mut *tx)
+                        .await?;
+
+                        info!(
+                            "[unit={unit_id}] Request attempt {} failed after {} attempts; giving up and moving it to dead letters",
+                            &attempt.request_attempt__id, &attempt.retry_count,
+                        );
+                    }
                 }
             }
 
@@ -452,6 +587,270 @@ async fn look_for_work(
     }
 }
 
+/// Verify the endpoints of subscriptions that wait for verification, independently from request attempts
+async fn verify_endpoints(
+    config: &webhook_delivery::Config,
+    pool: &PgPool,
+    worker_type: &WorkerType,
+) -> anyhow::Result<()> {
+    info!("Begin verifying endpoints");
+    loop {
+        if !verify_next_endpoint(config, pool, worker_type).await? {
+            trace!("No endpoint to verify");
+            sleep(ENDPOINT_VERIFICATION_POLLING_SLEEP).await;
+        }
+    }
+}
+
+/// Send its challenge to the endpoint of a subscription that waits for verification (if there is one), and enable the subscription if the endpoint echoed it; return false if there was no endpoint to verify
+async fn verify_next_endpoint(
+    config: &webhook_delivery::Config,
+    pool: &PgPool,
+    worker_type: &WorkerType,
+) -> anyhow::Result<bool> {
+    #[allow(non_snake_case)]
+    struct PendingVerification {
+        subscription__id: Uuid,
+        secret: Uuid,
+        challenge: String,
+        target: serde_json::Value,
+        attempt_count: i32,
+    }
+
+    // The subscription is claimed by a single statement so that no lock is held while the endpoint is called; other workers will not pick it before the retry delay
+    let retry_delay_in_s = ENDPOINT_VERIFICATION_RETRY_DELAY.as_secs_f64();
+    let pending = match worker_type {
+        WorkerType::Public { worker_id } => {
+            query_as!(
+                PendingVerification,
+                "
+                    UPDATE webhook.subscription AS claimed
+                    SET endpoint_verification_checked_at = statement_timestamp(), endpoint_verification_attempt_count = claimed.endpoint_verification_attempt_count + 1
+                    FROM (
+                        SELECT s.subscription__id, t.target
+                        FROM webhook.subscription AS s
+                        LEFT JOIN webhook.subscription__worker AS sw ON sw.subscription__id = s.subscription__id
+                        INNER JOIN event.application AS a ON a.application__id = s.application__id
+                        INNER JOIN iam.organization AS o ON o.organization__id = a.organization__id
+                        LEFT JOIN iam.organization__worker AS ow ON ow.organization__id = o.organization__id AND ow.default = true
+                        INNER JOIN webhook.target_definition AS t ON t.target__id = s.target__id AND t.target_type = 'http'
+                        WHERE s.endpoint_verification_status = 'pending' AND s.deleted_at IS NULL AND (COALESCE(sw.worker__id, ow.worker__id) IS NULL OR COALESCE(sw.worker__id, ow.worker__id) = $1)
+                            AND (s.endpoint_verification_checked_at IS NULL OR s.endpoint_verification_checked_at <= statement_timestamp() - make_interval(secs => $2))
+                        LIMIT 1
+                        FOR UPDATE OF s
+                        SKIP LOCKED
+                    ) AS pending
+                    WHERE claimed.subscription__id = pending.subscription__id
+                    RETURNING claimed.subscription__id, claimed.secret, claimed.endpoint_verification_challenge AS \"challenge!\", pending.target AS \"target!\", claimed.endpoint_verification_attempt_count AS attempt_count
+                ",
+                worker_id.to_owned(),
+                retry_delay_in_s,
+            )
+            .fetch_optional(pool)
+            .await?
+        }
+        WorkerType::Private { worker_id } => {
+            query_as!(
+                PendingVerification,
+                "
+                    UPDATE webhook.subscription AS claimed
+                    SET endpoint_verification_checked_at = statement_timestamp(), endpoint_verification_attempt_count = claimed.endpoint_verification_attempt_count + 1
+                    FROM (
+                        SELECT s.subscription__id, t.target
+                        FROM webhook.subscription AS s
+                        LEFT JOIN webhook.subscription__worker AS sw ON sw.subscription__id = s.subscription__id
+                        INNER JOIN event.application AS a ON a.application__id = s.application__id
+                        INNER JOIN iam.organization AS o ON o.organization__id = a.organization__id
+                        LEFT JOIN iam.organization__worker AS ow ON ow.organization__id = o.organization__id AND ow.default = true
+                        INNER JOIN webhook.target_definition AS t ON t.target__id = s.target__id AND t.target_type = 'http'
+                        WHERE s.endpoint_verification_status = 'pending' AND s.deleted_at IS NULL AND (COALESCE(sw.worker__id, ow.worker__id) = $1)
+                            AND (s.endpoint_verification_checked_at IS NULL OR s.endpoint_verification_checked_at <= statement_timestamp() - make_interval(secs => $2))
+                        LIMIT 1
+                        FOR UPDATE OF s
+                        SKIP LOCKED
+                    ) AS pending
+                    WHERE claimed.subscription__id = pending.subscription__id
+                    RETURNING claimed.subscription__id, claimed.secret, claimed.endpoint_verification_challenge AS \"challenge!\", pending.target AS \"target!\", claimed.endpoint_verification_attempt_count AS attempt_count
+                ",
+                &worker_id,
+                retry_delay_in_s,
+            )
+            .fetch_optional(pool)
+            .await?
+        }
+    };
+
+    let pending = match pending {
+        Some(pending) => pending,
+        None => return Ok(false),
+    };
+
+    debug!(
+        "Verifying endpoint of subscription {} (attempt {})",
+        &pending.subscription__id, pending.attempt_count
+    );
+    let verification = match serde_json::from_value::<Target>(pending.target) {
+        Ok(Target::Http(target)) => {
+            verify_endpoint(config, &target, &pending.secret, &pending.challenge).await
+        }
+        Ok(_) => Err(EndpointVerificationError::Failed(
+            "Only HTTP endpoints can be verified".to_owned(),
+        )),
+        Err(e) => Err(EndpointVerificationError::Failed(e.to_string())),
+    };
+
+    // Results are only stored if the subscription still waits for the same challenge (it might have been updated in the meantime)
+    match verification {
+        Ok(()) => {
+            query!(
+                "
+                    UPDATE webhook.subscription
+                    SET endpoint_verification_status = 'verified', endpoint_verification_challenge = NULL, endpoint_verification_checked_at = statement_timestamp(), endpoint_verification_error = NULL,
+                        is_enabled = is_enabled OR enable_on_endpoint_verification, enable_on_endpoint_verification = false
+                    WHERE subscription__id = $1 AND endpoint_verification_status = 'pending' AND endpoint_verification_challenge = $2
+                ",
+                pending.subscription__id,
+                &pending.challenge,
+            )
+            .execute(pool)
+            .await?;
+            info!(
+                "Endpoint of subscription {} was verified",
+                &pending.subscription__id
+            );
+        }
+        Err(EndpointVerificationError::Transient(e))
+            if pending.attempt_count < ENDPOINT_VERIFICATION_MAX_ATTEMPTS =>
+        {
+            query!(
+                "
+                    UPDATE webhook.subscription
+                    SET endpoint_verification_error = $3
+                    WHERE subscription__id = $1 AND endpoint_verification_status = 'pending' AND endpoint_verification_challenge = $2
+                ",
+                pending.subscription__id,
+                &pending.challenge,
+                &e,
+            )
+            .execute(pool)
+            .await?;
+            warn!(
+                "Endpoint of subscription {} could not be verified (will try again): {e}",
+                &pending.subscription__id
+            );
+        }
+        Err(e) => {
+            query!(
+                "
+                    UPDATE webhook.subscription
+                    SET endpoint_verification_status = 'failed', endpoint_verification_challenge = NULL, endpoint_verification_checked_at = statement_timestamp(), endpoint_verification_error = $3
+                    WHERE subscription__id = $1 AND endpoint_verification_status = 'pending' AND endpoint_verification_challenge = $2
+                ",
+                pending.subscription__id,
+                &pending.challenge,
+                e.message(),
+            )
+            .execute(pool)
+            .await?;
+            warn!(
+                "Endpoint of subscription {} could not be verified: {}",
+                &pending.subscription__id,
+                e.message()
+            );
+        }
+    }
+
+    Ok(true)
+}
+
+/// Take an in-flight slot and a request of the current second if the subscription of the attempt has throughput limits; return false if one of them is not available
+async fn acquire_throughput_slot(
+    conn: &mut PgConnection,
+    pool: &PgPool,
+    attempt: &RequestAttempt,
+) -> Result<bool, sqlx::Error> {
+    if let Some(max_in_flight_requests) = attempt.max_in_flight_requests {
+        // Slots are transaction-level advisory locks: they are released when the response is stored, or if the worker dies
+        let slot = query!(
+            "
+                SELECT slot AS \"slot!\"
+                FROM webhook.subscription AS s, generate_series(0, $2 - 1) AS slot
+                WHERE s.subscription__id = $1 AND pg_try_advisory_xact_lock(s.throughput_lock_id * 1000 + slot)
+                LIMIT 1
+            ",
+            attempt.subscription__id,
+            max_in_flight_requests,
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        if slot.is_none() {
+            return Ok(false);
+        }
+    }
+
+    if let Some(max_requests_per_second) = attempt.max_requests_per_second {
+        // The counter must be visible to other workers right away, so it is not updated in the transaction
+        let request = query!(
+            "
+                INSERT INTO webhook.subscription__delivery_rate AS dr (subscription__id, window_started_at, request_count)
+                VALUES ($1, date_trunc('second', statement_timestamp()), 1)
+                ON CONFLICT (subscription__id) DO UPDATE
+                SET window_started_at = excluded.window_started_at,
+                    request_count = CASE WHEN dr.window_started_at = excluded.window_started_at THEN dr.request_count + 1 ELSE 1 END
+                WHERE dr.window_started_at <> excluded.window_started_at OR dr.request_count < $2
+                RETURNING dr.request_count
+            ",
+            attempt.subscription__id,
+            max_requests_per_second,
+        )
+        .fetch_optional(pool)
+        .await?;
+        if request.is_none() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Lock other pending attempts of the same batched subscription, in the order their events were received (with ordered delivery, only attempts that are first for their ordering key can be sent along)
+async fn fetch_batch_mates(
+    conn: &mut PgConnection,
+    attempt: &RequestAttempt,
+    limit: i32,
+) -> Result<Vec<RequestAttempt>, sqlx::Error> {
+    query_as!(
+        RequestAttempt,
+        "
+            SELECT ra.request_attempt__id, ra.event__id, ra.subscription__id, ra.created_at, ra.retry_count, t.target AS \"target!\", e.event_type__name, e.payload AS payload, e.payload_content_type AS payload_content_type, e.occurred_at, e.labels, e.metadata, s.secret, s.transformation, s.batch_max_count, s.batch_max_size_in_bytes, e.expires_at, s.delivery_schedule, s.max_requests_per_second, s.max_in_flight_requests
+            FROM webhook.request_attempt AS ra
+            INNER JOIN webhook.subscription AS s ON s.subscription__id = ra.subscription__id
+            INNER JOIN webhook.target_definition AS t ON t.target__id = s.target__id AND t.target_type <> 'pull'
+            INNER JOIN event.event AS e ON e.event__id = ra.event__id
+            WHERE ra.subscription__id = $1 AND ra.request_attempt__id <> $2
+                AND ra.succeeded_at IS NULL AND ra.failed_at IS NULL AND (ra.delay_until IS NULL OR ra.delay_until <= statement_timestamp())
+                AND (e.expires_at IS NULL OR e.expires_at > statement_timestamp())
+                AND (NOT s.ordered_delivery OR NOT EXISTS (
+                    SELECT 1
+                    FROM webhook.request_attempt AS prev_ra
+                    INNER JOIN event.event AS prev_e ON prev_e.event__id = prev_ra.event__id
+                    WHERE prev_ra.subscription__id = ra.subscription__id AND prev_ra.succeeded_at IS NULL AND prev_ra.failed_at IS NULL
+                        AND (prev_e.received_at, prev_e.event__id) < (e.received_at, e.event__id)
+                        AND (s.ordering_key_label IS NULL OR prev_e.labels ->> s.ordering_key_label IS NOT DISTINCT FROM e.labels ->> s.ordering_key_label)
+                ))
+            ORDER BY e.received_at ASC, e.event__id ASC
+            LIMIT $3
+            FOR UPDATE OF ra
+            SKIP LOCKED
+        ",
+        attempt.subscription__id,
+        attempt.request_attempt__id,
+        i64::from(limit),
+    )
+    .fetch_all(conn)
+    .await
+}
+
 async fn get_worker_type(worker_name: &str, conn: &PgPool) -> Result<WorkerType, sqlx::Error> {
     #[allow(non_snake_case)]
     struct Worker {
@@ -487,9 +886,10 @@ async fn get_worker_type(worker_name: &str, conn: &PgPool) -> Result<WorkerType,
     }
 }
 
-async fn compute_next_retry<'a>(
+async fn compute_next_retry(
     conn: &mut PgConnection,
     subscription_id: &Uuid,
+    expires_at: Option<DateTime<Utc>>,
     max_fast_retries: u32,
     max_slow_retries: u32,
     retry_count: i16,
@@ -506,17 +906,29 @@ async fn compute_next_retry<'a>(
     .await?;
 
     if sub.is_some() {
-        Ok(compute_next_retry_duration(
-            max_fast_retries,
-            max_slow_retries,
-            retry_count,
-        ))
+        Ok(
+            compute_next_retry_duration(max_fast_retries, max_slow_retries, retry_count)
+                .filter(|retry_in| !expires_before_retry(expires_at, *retry_in, Utc::now())),
+        )
     } else {
         // If the subscription was disabled or soft-deleted, we do not want to schedule a next attempt
         Ok(None)
     }
 }
 
+/// Whether an event will have expired when a retry scheduled in `retry_in` gets picked
+fn expires_before_retry(
+    expires_at: Option<DateTime<Utc>>,
+    retry_in: Duration,
+    now: DateTime<Utc>,
+) -> bool {
+    match (expires_at, chrono::Duration::from_std(retry_in)) {
+        (Some(expires_at), Ok(retry_in)) => expires_at <= now + retry_in,
+        (Some(expires_at), Err(_)) => expires_at <= now,
+        (None, _) => false,
+    }
+}
+
 fn compute_next_retry_duration(
     max_fast_retries: u32,
     max_slow_retries: u32,
@@ -535,3 +947,28 @@ fn compute_next_retry_duration(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::prelude::*;
+
+    #[test]
+    fn retry_after_expiration() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 13, 10, 0, 0).unwrap();
+        let expires_at = Utc.with_ymd_and_hms(2024, 5, 13, 10, 30, 0).unwrap();
+
+        assert!(!expires_before_retry(None, SLOW_RETRY_DELAY, now));
+        assert!(!expires_before_retry(
+            Some(expires_at),
+            MAXIMUM_FAST_RETRY_DELAY,
+            now
+        ));
+        assert!(expires_before_retry(
+            Some(expires_at),
+            SLOW_RETRY_DELAY,
+            now
+        ));
+    }
+}
@@ -0,0 +1,284 @@
+//! Transformation of event payloads, based on JSON templates (see README)
+
+use base64::engine::general_purpose::STANDARD as Base64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Maximum number of nested arrays and objects in a template
+const MAX_DEPTH: usize = 20;
+
+const PLACEHOLDER_START: &str = "{{";
+const PLACEHOLDER_END: &str = "}}";
+
+/// Event properties that placeholders can refer to
+pub const ROOTS: [&str; 6] = [
+    "event_id",
+    "event_type",
+    "occurred_at",
+    "labels",
+    "metadata",
+    "payload",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum TransformationError {
+    #[error("Template cannot have more than {MAX_DEPTH} levels of nested arrays and objects")]
+    TooDeep,
+    #[error("Placeholder is not terminated in '{0}'")]
+    UnterminatedPlaceholder(String),
+    #[error("Placeholder '{0}' is not a valid path")]
+    InvalidPath(String),
+    #[error("Placeholder '{0}' must start with one of: {roots}", roots = ROOTS.join(", "))]
+    UnknownRoot(String),
+}
+
+/// Event that a transformation is rendered for
+#[derive(Debug, Clone)]
+pub struct Event<'a> {
+    pub id: Uuid,
+    pub event_type: &'a str,
+    pub occurred_at: DateTime<Utc>,
+    pub labels: &'a Value,
+    pub metadata: Option<&'a Value>,
+    pub payload: &'a [u8],
+    pub payload_content_type: &'a str,
+}
+
+impl Event<'_> {
+    /// JSON representation of the event, which is what placeholders refer to
+    pub fn to_document(&self) -> Value {
+        let payload = match self.payload_content_type {
+            "application/json" => serde_json::from_slice(self.payload)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(self.payload).into())),
+            "text/plain" => Value::String(String::from_utf8_lossy(self.payload).into()),
+            _ => Value::String(Base64.encode(self.payload)),
+        };
+
+        Value::Object(Map::from_iter([
+            ("event_id".to_owned(), Value::String(self.id.to_string())),
+            (
+                "event_type".to_owned(),
+                Value::String(self.event_type.to_owned()),
+            ),
+            (
+                "occurred_at".to_owned(),
+                Value::String(self.occurred_at.to_rfc3339()),
+            ),
+            ("labels".to_owned(), self.labels.to_owned()),
+            (
+                "metadata".to_owned(),
+                self.metadata
+                    .cloned()
+                    .unwrap_or_else(|| Value::Object(Map::new())),
+            ),
+            ("payload".to_owned(), payload),
+        ]))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transformation(Value);
+
+impl Transformation {
+    /// Check that a template is valid
+    pub fn new(template: Value) -> Result<Self, TransformationError> {
+        check(&template, 0)?;
+        Ok(Self(template))
+    }
+
+    /// Render the template for an event; rendering a valid template cannot fail
+    pub fn render(&self, event: &Event) -> Value {
+        render(&self.0, &event.to_document())
+    }
+
+    /// Render the template for an event, as a JSON body
+    pub fn render_to_vec(&self, event: &Event) -> Vec<u8> {
+        self.render(event).to_string().into_bytes()
+    }
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(Vec<&'a str>),
+}
+
+fn parse(str: &str) -> Result<Vec<Part<'_>>, TransformationError> {
+    let mut parts = vec![];
+    let mut rest = str;
+
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        let after_start = &rest[start + PLACEHOLDER_START.len()..];
+        let end = after_start
+            .find(PLACEHOLDER_END)
+            .ok_or_else(|| TransformationError::UnterminatedPlaceholder(str.to_owned()))?;
+
+        let path = after_start[..end].trim();
+        let segments = path.split('.').collect::<Vec<_>>();
+        if segments.iter().any(|s| {
+            s.is_empty()
+                || !s
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        }) {
+            return Err(TransformationError::InvalidPath(path.to_owned()));
+        }
+        if !ROOTS.contains(&segments[0]) {
+            return Err(TransformationError::UnknownRoot(path.to_owned()));
+        }
+        parts.push(Part::Placeholder(segments));
+
+        rest = &after_start[end + PLACEHOLDER_END.len()..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+
+    Ok(parts)
+}
+
+fn check(template: &Value, depth: usize) -> Result<(), TransformationError> {
+    match template {
+        Value::String(str) => parse(str).map(|_| ()),
+        Value::Array(_) | Value::Object(_) if depth >= MAX_DEPTH => {
+            Err(TransformationError::TooDeep)
+        }
+        Value::Array(items) => items.iter().try_for_each(|v| check(v, depth + 1)),
+        Value::Object(properties) => properties.values().try_for_each(|v| check(v, depth + 1)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => Ok(()),
+    }
+}
+
+fn lookup<'a>(document: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(document, |value, segment| match value {
+            Value::Object(properties) => properties.get(*segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+fn render(template: &Value, document: &Value) -> Value {
+    match template {
+        Value::String(str) => {
+            // Templates were checked when transformation was created
+            let parts = parse(str).unwrap_or_default();
+            match parts.as_slice() {
+                [Part::Placeholder(path)] => lookup(document, path).cloned().unwrap_or(Value::Null),
+                _ => Value::String(
+                    parts
+                        .iter()
+                        .map(|part| match part {
+                            Part::Text(text) => (*text).to_owned(),
+                            Part::Placeholder(path) => match lookup(document, path) {
+                                Some(Value::String(s)) => s.to_owned(),
+                                Some(Value::Null) | None => String::new(),
+                                Some(v) => v.to_string(),
+                            },
+                        })
+                        .collect(),
+                ),
+            }
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|v| render(v, document)).collect()),
+        Value::Object(properties) => Value::Object(
+            properties
+                .iter()
+                .map(|(k, v)| (k.to_owned(), render(v, document)))
+                .collect(),
+        ),
+        Value::Null | Value::Bool(_) | Value::Number(_) => template.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn render_for(template: Value, payload: &[u8], payload_content_type: &str) -> Value {
+        let labels = json!({ "env": "prod" });
+        let event = Event {
+            id: Uuid::nil(),
+            event_type: "billing.invoice.paid",
+            occurred_at: Utc.with_ymd_and_hms(2024, 7, 22, 10, 0, 0).unwrap(),
+            labels: &labels,
+            metadata: None,
+            payload,
+            payload_content_type,
+        };
+        Transformation::new(template).unwrap().render(&event)
+    }
+
+    #[test]
+    fn render_json_payload() {
+        let payload =
+            br#"{"invoice": {"id": 42, "lines": [{"label": "Pro plan"}]}, "customer": "ACME"}"#;
+        let template = json!({
+            "text": "Invoice {{ payload.invoice.id }} of {{payload.customer}} was paid ({{ labels.env }})",
+            "invoice": "{{ payload.invoice }}",
+            "first_line": "{{ payload.invoice.lines.0.label }}",
+            "missing": "{{ payload.nope }}",
+            "type": "{{ event_type }}",
+            "constant": [1, true, null],
+        });
+
+        assert_eq!(
+            render_for(template, payload, "application/json"),
+            json!({
+                "text": "Invoice 42 of ACME was paid (prod)",
+                "invoice": { "id": 42, "lines": [{ "label": "Pro plan" }] },
+                "first_line": "Pro plan",
+                "missing": null,
+                "type": "billing.invoice.paid",
+                "constant": [1, true, null],
+            })
+        );
+    }
+
+    #[test]
+    fn render_other_payloads() {
+        assert_eq!(
+            render_for(json!("{{ payload }}"), b"hello", "text/plain"),
+            json!("hello")
+        );
+        assert_eq!(
+            render_for(
+                json!({ "data": "{{ payload }}" }),
+                b"hello",
+                "application/octet-stream+base64"
+            ),
+            json!({ "data": "aGVsbG8=" })
+        );
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert_eq!(
+            Transformation::new(json!("{{ payload.id")),
+            Err(TransformationError::UnterminatedPlaceholder(
+                "{{ payload.id".to_owned()
+            ))
+        );
+        assert_eq!(
+            Transformation::new(json!({ "a": "{{ payload..id }}" })),
+            Err(TransformationError::InvalidPath("payload..id".to_owned()))
+        );
+        assert_eq!(
+            Transformation::new(json!(["{{ secret }}"])),
+            Err(TransformationError::UnknownRoot("secret".to_owned()))
+        );
+
+        let mut deep = json!(1);
+        for _ in 0..=MAX_DEPTH {
+            deep = json!([deep]);
+        }
+        assert_eq!(Transformation::new(deep), Err(TransformationError::TooDeep));
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::kafka::KafkaClientKey;
+
+/// Connections that were not used for this long are dropped (which closes them)
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Connections to AMQP brokers and clients of Kafka clusters, kept open between deliveries to the same target
+///
+/// Clones share the same connections, so a worker should create it once and use it for all its deliveries.
+#[derive(Debug, Clone, Default)]
+pub struct BrokerConnections {
+    pub(crate) amqp: Arc<ConnectionCache<String, lapin::Connection>>,
+    pub(crate) kafka: Arc<ConnectionCache<KafkaClientKey, rskafka::client::Client>>,
+}
+
+pub(crate) struct ConnectionCache<K, C> {
+    connections: Mutex<HashMap<K, (Arc<C>, Instant)>>,
+}
+
+impl<K, C> Default for ConnectionCache<K, C> {
+    fn default() -> Self {
+        Self {
+            connections: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K, C> ConnectionCache<K, C> {
+    fn len(&self) -> usize {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+}
+
+impl<K, C> fmt::Debug for ConnectionCache<K, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionCache")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<K: Eq + Hash, C> ConnectionCache<K, C> {
+    /// Get the connection of a target, if there is one that is usable
+    pub(crate) fn get(&self, key: &K, is_usable: impl Fn(&C) -> bool) -> Option<Arc<C>> {
+        let mut connections = self
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        connections.retain(|_, (_, used_at)| now.duration_since(*used_at) < IDLE_TIMEOUT);
+
+        match connections.get_mut(key) {
+            Some((connection, used_at)) if is_usable(connection) => {
+                *used_at = now;
+                Some(connection.clone())
+            }
+            Some(_) => {
+                connections.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Keep the connection of a target for next deliveries (replacing the previous one, if any)
+    pub(crate) fn insert(&self, key: K, connection: C) -> Arc<C> {
+        let connection = Arc::new(connection);
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, (connection.clone(), Instant::now()));
+        connection
+    }
+
+    /// Forget the connection of a target, for example after an error, so that next deliveries open a new one
+    pub(crate) fn remove(&self, key: &K) {
+        self.connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_reuses_usable_connections() {
+        let cache = ConnectionCache::<&str, u8>::default();
+        assert!(cache.get(&"a", |_| true).is_none());
+
+        let inserted = cache.insert("a", 1);
+        let cached = cache.get(&"a", |_| true).unwrap();
+        assert!(Arc::ptr_eq(&inserted, &cached));
+
+        // Unusable connections are dropped
+        assert!(cache.get(&"a", |_| false).is_none());
+        assert_eq!(cache.len(), 0);
+
+        cache.insert("b", 2);
+        cache.remove(&"b");
+        assert!(cache.get(&"b", |_| true).is_none());
+    }
+}
@@ -0,0 +1,319 @@
+use chrono::Utc;
+use log::trace;
+use reqwest::header::HeaderMap;
+use rskafka::client::partition::{Compression, UnknownTopicHandling};
+use rskafka::client::{ClientBuilder, SaslConfig};
+use rskafka::record::Record;
+use rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+use tokio::time::timeout;
+
+use crate::work::{ResponseError, TIMEOUT};
+use crate::{Config, RequestAttempt};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct KafkaTarget {
+    pub brokers: Vec<String>,
+    pub topic: String,
+    pub partition_key_label: Option<String>,
+    pub sasl_username: Option<String>,
+    pub sasl_password: Option<String>,
+    pub tls: bool,
+}
+
+/// Settings that clients of a Kafka cluster are created from (the same client is used for all topics)
+pub(crate) type KafkaClientKey = (Vec<String>, Option<String>, Option<String>, bool);
+
+/// Produce a record to a Kafka topic and wait for the partition leader to acknowledge it
+///
+/// Records whose event has the target's partition key label use its value as key and go to the same partition as they would with Kafka's default partitioner; other records are spread over partitions according to their event ID.
+pub async fn produce(
+    config: &Config,
+    attempt: &RequestAttempt,
+    target: &KafkaTarget,
+    body: Vec<u8>,
+    headers: &HeaderMap,
+) -> Result<String, (ResponseError, String)> {
+    // The client connects to the brokers advertised by the cluster, whose addresses cannot be checked before connecting, so Kafka targets are only allowed if the target IP check is disabled
+    if !config.disable_target_ip_check {
+        return Err((
+            ResponseError::InvalidTarget,
+            "Kafka targets can only be used if the worker's target IP check is disabled".to_owned(),
+        ));
+    }
+
+    let key = target
+        .partition_key_label
+        .as_deref()
+        .and_then(|label| attempt.label(label))
+        .map(|value| value.as_bytes().to_vec());
+    let record = Record {
+        key: key.clone(),
+        value: Some(body),
+        headers: headers
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+            .collect::<BTreeMap<_, _>>(),
+        timestamp: Utc::now(),
+    };
+
+    let clients = &config.broker_connections.kafka;
+    let client_key = (
+        target.brokers.to_owned(),
+        target.sasl_username.to_owned(),
+        target.sasl_password.to_owned(),
+        target.tls,
+    );
+    let production = async {
+        let client = match clients.get(&client_key, |_| true) {
+            Some(client) => client,
+            None => {
+                trace!("Creating Kafka client");
+                let mut builder = ClientBuilder::new(target.brokers.to_owned())
+                    .client_id(config.user_agent.as_str());
+                if target.tls {
+                    builder = builder.tls_config(tls_config());
+                }
+                if let (Some(username), Some(password)) =
+                    (&target.sasl_username, &target.sasl_password)
+                {
+                    builder = builder.sasl_config(SaslConfig::Plain {
+                        username: username.to_owned(),
+                        password: password.to_owned(),
+                    });
+                }
+                let client = builder
+                    .build()
+                    .await
+                    .map_err(|e| (ResponseError::Connection, e.to_string()))?;
+                clients.insert(client_key.clone(), client)
+            }
+        };
+
+        let partitions = client
+            .list_topics()
+            .await
+            .map_err(|e| (ResponseError::Unknown, e.to_string()))?
+            .into_iter()
+            .find(|topic| topic.name == target.topic)
+            .map(|topic| topic.partitions.into_iter().collect::<Vec<_>>())
+            .filter(|partitions| !partitions.is_empty())
+            .ok_or_else(|| {
+                (
+                    ResponseError::InvalidTarget,
+                    format!("Topic '{}' does not exist", &target.topic),
+                )
+            })?;
+        let hash = match &key {
+            Some(key) => (murmur2(key) & 0x7fffffff) as usize,
+            None => attempt.event__id.as_u128() as usize,
+        };
+        let partition = partitions[hash % partitions.len()];
+
+        trace!(
+            "Kafka produce to topic '{}' (partition {partition})",
+            &target.topic
+        );
+        let offsets = client
+            .partition_client(&target.topic, partition, UnknownTopicHandling::Error)
+            .await
+            .map_err(|e| (ResponseError::Unknown, e.to_string()))?
+            .produce(vec![record], Compression::NoCompression)
+            .await
+            .map_err(|e| (ResponseError::Unknown, e.to_string()))?;
+
+        Ok(format!(
+            "Record produced to topic '{}' (partition {partition}, offset {})",
+            &target.topic,
+            offsets.first().copied().unwrap_or(-1)
+        ))
+    };
+
+    let result = timeout(TIMEOUT, production).await.unwrap_or_else(|_| {
+        Err((
+            ResponseError::Timeout,
+            "Production to Kafka timed out".to_owned(),
+        ))
+    });
+    if matches!(
+        result,
+        Err((ResponseError::Unknown | ResponseError::Timeout, _))
+    ) {
+        clients.remove(&client_key);
+    }
+    result
+}
+
+fn tls_config() -> Arc<ClientConfig> {
+    static TLS_CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    TLS_CONFIG
+        .get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+            Arc::new(
+                ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+/// Kafka's flavour of MurmurHash2, which its default partitioner uses on record keys
+fn murmur2(data: &[u8]) -> i32 {
+    const SEED: u32 = 0x9747b28c;
+    const M: u32 = 0x5bd1e995;
+    const R: u32 = 24;
+
+    let mut h = SEED ^ data.len() as u32;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    if tail.len() >= 3 {
+        h ^= (tail[2] as u32) << 16;
+    }
+    if tail.len() >= 2 {
+        h ^= (tail[1] as u32) << 8;
+    }
+    if !tail.is_empty() {
+        h ^= tail[0] as u32;
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+
+    h as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{work, BrokerConnections};
+
+    #[test]
+    fn murmur2_matches_kafka() {
+        // Values from Kafka's own test suite
+        assert_eq!(murmur2(b"21"), -973932308);
+        assert_eq!(murmur2(b"foobar"), -790332482);
+        assert_eq!(murmur2(b"a-little-bit-long-string"), -985981536);
+        assert_eq!(murmur2(b"a-little-bit-longer-string"), -1486304829);
+        assert_eq!(
+            murmur2(b"lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8"),
+            -58897971
+        );
+        assert_eq!(murmur2(b"abc"), 479470107);
+    }
+
+    /// Needs a Kafka cluster, such as the `kafka` service of the Docker Compose file (`docker compose --profile brokers up kafka`), whose bootstrap broker is given in `TEST_KAFKA_BROKER` (for example: `localhost:9092`; the `kafka` host name advertised by the Docker Compose service must resolve to the container); the test is skipped if it is not set
+    #[tokio::test]
+    async fn produce_to_kafka() {
+        let Ok(broker) = std::env::var("TEST_KAFKA_BROKER") else {
+            eprintln!("TEST_KAFKA_BROKER is not set: skipping test");
+            return;
+        };
+        let config = Config {
+            disable_target_ip_check: true,
+            user_agent: "hook0-test".to_owned(),
+            broker_connections: BrokerConnections::default(),
+        };
+
+        let topic = format!("hook0-test-{}", Uuid::new_v4());
+        let admin = ClientBuilder::new(vec![broker.to_owned()])
+            .build()
+            .await
+            .unwrap();
+        admin
+            .controller_client()
+            .unwrap()
+            .create_topic(&topic, 1, 1, 5_000)
+            .await
+            .unwrap();
+
+        let attempt = |tenant: &str| RequestAttempt {
+            request_attempt__id: Uuid::new_v4(),
+            event__id: Uuid::new_v4(),
+            subscription__id: Uuid::nil(),
+            created_at: Utc::now(),
+            retry_count: 0,
+            target: json!({ "type": "kafka", "brokers": [broker], "topic": topic, "partition_key_label": "tenant", "sasl_username": null, "sasl_password": null, "tls": false }),
+            event_type__name: "billing.invoice.paid".to_owned(),
+            payload: b"hello".to_vec(),
+            payload_content_type: "text/plain".to_owned(),
+            occurred_at: Utc::now(),
+            labels: json!({ "tenant": tenant }),
+            metadata: None,
+            secret: Uuid::nil(),
+            transformation: None,
+            batch_max_count: None,
+            batch_max_size_in_bytes: None,
+            expires_at: None,
+            delivery_schedule: None,
+            max_requests_per_second: None,
+            max_in_flight_requests: None,
+        };
+        let client_key = (vec![broker.to_owned()], None, None, false);
+
+        assert!(work(&config, &attempt("acme")).await.is_success());
+        let client = config
+            .broker_connections
+            .kafka
+            .get(&client_key, |_| true)
+            .unwrap();
+
+        // The client is reused by the next productions to the same cluster
+        assert!(work(&config, &attempt("globex")).await.is_success());
+        assert!(Arc::ptr_eq(
+            &client,
+            &config
+                .broker_connections
+                .kafka
+                .get(&client_key, |_| true)
+                .unwrap()
+        ));
+
+        let (records, _) = admin
+            .partition_client(&topic, 0, UnknownTopicHandling::Error)
+            .await
+            .unwrap()
+            .fetch_records(0, 1..1_000_000, 1_000)
+            .await
+            .unwrap();
+        assert_eq!(
+            records
+                .into_iter()
+                .map(|r| (r.record.key, r.record.value))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(b"acme".to_vec()), Some(b"hello".to_vec())),
+                (Some(b"globex".to_vec()), Some(b"hello".to_vec())),
+            ]
+        );
+
+        // Kafka targets cannot be used while the target IP check is enabled
+        let config = Config {
+            disable_target_ip_check: false,
+            ..config
+        };
+        assert!(!work(&config, &attempt("acme")).await.is_success());
+    }
+}
@@ -0,0 +1,148 @@
+//! Delivery of events to the targets of subscriptions (HTTP endpoints, AMQP exchanges and Kafka topics)
+//!
+//! This is used by the output worker, as well as by the API to send test events synchronously.
+
+mod amqp;
+mod connections;
+mod kafka;
+mod schedule;
+mod work;
+
+use chrono::{DateTime, Utc};
+use log::error;
+use payload_transformation::{Transformation, TransformationError};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub use amqp::AmqpTarget;
+pub use connections::BrokerConnections;
+pub use kafka::KafkaTarget;
+pub use schedule::*;
+pub use work::*;
+
+/// How long attempts are deferred when the next opening of a delivery schedule cannot be determined
+const SCHEDULE_FALLBACK_DELAY: chrono::Duration = chrono::Duration::days(7);
+
+/// Settings of deliveries
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// If set to false, deliveries to targets that resolve to IPs that are not globally reachable (like "127.0.0.1" for example) fail
+    pub disable_target_ip_check: bool,
+    /// User agent of HTTP requests (also used as client ID by Kafka)
+    pub user_agent: String,
+    /// Connections to brokers that are reused between deliveries
+    pub broker_connections: BrokerConnections,
+}
+
+#[derive(Debug, Clone)]
+#[allow(non_snake_case)]
+pub struct RequestAttempt {
+    pub request_attempt__id: Uuid,
+    pub event__id: Uuid,
+    pub subscription__id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub retry_count: i16,
+    pub target: serde_json::Value,
+    pub event_type__name: String,
+    pub payload: Vec<u8>,
+    pub payload_content_type: String,
+    pub occurred_at: DateTime<Utc>,
+    pub labels: serde_json::Value,
+    pub metadata: Option<serde_json::Value>,
+    pub secret: Uuid,
+    pub transformation: Option<serde_json::Value>,
+    pub batch_max_count: Option<i32>,
+    pub batch_max_size_in_bytes: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub delivery_schedule: Option<serde_json::Value>,
+    pub max_requests_per_second: Option<i32>,
+    pub max_in_flight_requests: Option<i32>,
+}
+
+/// Where webhooks of a subscription are sent (pull targets are not delivered to: consumers fetch their events)
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Target {
+    Http(HttpTarget),
+    Amqp(AmqpTarget),
+    Kafka(KafkaTarget),
+}
+
+impl RequestAttempt {
+    /// Parse target of the subscription from JSON
+    pub fn target(&self) -> serde_json::Result<Target> {
+        serde_json::from_value(self.target.clone())
+    }
+
+    /// Value of one of the event's labels, if it has it
+    pub fn label(&self, key: &str) -> Option<&str> {
+        self.labels.get(key).and_then(|v| v.as_str())
+    }
+
+    /// Whether the event expired, in which case the webhook must not be called
+    pub fn has_expired(&self) -> bool {
+        self.expires_at.is_some_and(|e| e <= Utc::now())
+    }
+
+    /// If the subscription has a delivery schedule that is closed at `now`, the instant from which the attempt can be sent
+    pub fn deferred_until(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // Expired attempts are not sent anyway
+        if self.has_expired() {
+            return None;
+        }
+
+        let schedule =
+            match serde_json::from_value::<DeliverySchedule>(self.delivery_schedule.clone()?) {
+                Ok(schedule) => schedule,
+                // The schedule may forbid deliveries right now, so the attempt is deferred rather than sent
+                Err(e) => {
+                    error!(
+                        "Invalid delivery schedule of subscription {}; deferring attempt: {e}",
+                        self.subscription__id
+                    );
+                    return Some(now + SCHEDULE_FALLBACK_DELAY);
+                }
+            };
+        match schedule.next_opening(now) {
+            Some(opening) if opening <= now => None,
+            Some(opening) => Some(opening),
+            // Schedule might be open again in more than a week
+            None => Some(now + SCHEDULE_FALLBACK_DELAY),
+        }
+    }
+
+    pub fn event(&self) -> payload_transformation::Event<'_> {
+        payload_transformation::Event {
+            id: self.event__id,
+            event_type: &self.event_type__name,
+            occurred_at: self.occurred_at,
+            labels: &self.labels,
+            metadata: self.metadata.as_ref(),
+            payload: &self.payload,
+            payload_content_type: &self.payload_content_type,
+        }
+    }
+
+    /// Body to send and its content type: the event payload, or the rendering of the subscription's transformation
+    pub fn body(&self) -> Result<(Vec<u8>, String), TransformationError> {
+        match &self.transformation {
+            Some(template) => {
+                let transformation = Transformation::new(template.to_owned())?;
+                let body = transformation.render_to_vec(&self.event());
+                Ok((body, "application/json".to_owned()))
+            }
+            None => Ok((self.payload.clone(), self.payload_content_type.clone())),
+        }
+    }
+
+    /// Item that represents the event in a batch: the event as a JSON document, or the rendering of the subscription's transformation
+    pub fn batch_item(&self) -> Result<serde_json::Value, TransformationError> {
+        match &self.transformation {
+            Some(template) => {
+                let transformation = Transformation::new(template.to_owned())?;
+                Ok(transformation.render(&self.event()))
+            }
+            None => Ok(self.event().to_document()),
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Deserializer};
+
+/// Maximum number of windows of each kind in a schedule
+pub const DELIVERY_SCHEDULE_MAX_WINDOWS: usize = 50;
+
+/// When deliveries of a subscription can happen
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DeliverySchedule {
+    /// IANA time zone in which windows are expressed
+    pub timezone: Tz,
+    /// If not empty, deliveries only happen during these windows
+    #[serde(default)]
+    pub open_windows: Vec<DeliveryWindow>,
+    /// Deliveries never happen during these windows
+    #[serde(default)]
+    pub maintenance_windows: Vec<DeliveryWindow>,
+}
+
+/// Weekly recurring window; if `end` is before `start`, the window ends on the next day
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DeliveryWindow {
+    pub days: Vec<Weekday>,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: NaiveTime,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: NaiveTime,
+}
+
+/// Parse the start or end of a window
+pub fn parse_window_time(str: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(str, "%H:%M")
+        .map_err(|_| format!("'{str}' is not a valid time (expected format: HH:MM)"))
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let str = String::deserialize(deserializer)?;
+    parse_window_time(&str).map_err(serde::de::Error::custom)
+}
+
+impl DeliverySchedule {
+    /// Check properties that cannot be expressed by types
+    pub fn check(&self) -> Result<(), &'static str> {
+        let windows = self
+            .open_windows
+            .iter()
+            .chain(self.maintenance_windows.iter());
+        if self.open_windows.len() > DELIVERY_SCHEDULE_MAX_WINDOWS
+            || self.maintenance_windows.len() > DELIVERY_SCHEDULE_MAX_WINDOWS
+        {
+            Err("Delivery schedule has too many windows")
+        } else if windows.clone().any(|w| w.days.is_empty()) {
+            Err("Windows of a delivery schedule must have at least one day")
+        } else if windows.clone().any(|w| w.start == w.end) {
+            Err("Windows of a delivery schedule must not start and end at the same time")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// First instant from `now` at which deliveries can happen, if there is one in the next week
+    pub fn next_opening(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        // Windows are considered on the days around the next week (a window can start the day before and end after midnight)
+        let today = now.with_timezone(&self.timezone).date_naive();
+        let days = (-1..=8)
+            .filter_map(|offset| today.checked_add_signed(Duration::days(offset)))
+            .collect::<Vec<_>>();
+        let intervals = |windows: &[DeliveryWindow]| {
+            windows
+                .iter()
+                .flat_map(|w| {
+                    days.iter()
+                        .filter(|d| w.days.contains(&d.weekday()))
+                        .filter_map(|d| self.window_interval(w, *d))
+                })
+                .collect::<Vec<_>>()
+        };
+        let open = intervals(&self.open_windows);
+        let maintenance = intervals(&self.maintenance_windows);
+
+        let is_open = |instant: &DateTime<Utc>| {
+            let contains =
+                |(start, end): &(DateTime<Utc>, DateTime<Utc>)| start <= instant && instant < end;
+            (self.open_windows.is_empty() || open.iter().any(contains))
+                && !maintenance.iter().any(contains)
+        };
+
+        // Deliveries can only become possible when an open window starts or when a maintenance window ends
+        let mut candidates = open
+            .iter()
+            .map(|(start, _)| *start)
+            .chain(maintenance.iter().map(|(_, end)| *end))
+            .filter(|instant| *instant > now)
+            .collect::<Vec<_>>();
+        candidates.push(now);
+        candidates.sort();
+        candidates.into_iter().find(is_open)
+    }
+
+    fn window_interval(
+        &self,
+        window: &DeliveryWindow,
+        day: NaiveDate,
+    ) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let end_day = if window.end > window.start {
+            day
+        } else {
+            day.succ_opt()?
+        };
+        let start = self.to_utc(day.and_time(window.start))?;
+        let end = self.to_utc(end_day.and_time(window.end))?;
+        Some((start, end))
+    }
+
+    /// Convert a local time to UTC; local times that do not exist (because of a DST transition) are moved forward
+    fn to_utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        (0..=2)
+            .find_map(|hours| {
+                self.timezone
+                    .from_local_datetime(&(local + Duration::hours(hours)))
+                    .earliest()
+            })
+            .map(|dt| dt.with_timezone(&Utc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn utc(str: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(str)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn business_hours() -> DeliverySchedule {
+        serde_json::from_value(json!({
+            "timezone": "Europe/Paris",
+            "open_windows": [{ "days": ["mon", "tue", "wed", "thu", "fri"], "start": "09:00", "end": "18:00" }],
+            "maintenance_windows": [{ "days": ["wed"], "start": "12:00", "end": "14:00" }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn opening_during_open_window() {
+        let now = utc("2024-09-10T08:00:00Z");
+        assert_eq!(business_hours().next_opening(now), Some(now));
+    }
+
+    #[test]
+    fn opening_after_weekend() {
+        // Saturday evening in Paris; next opening is Monday 09:00 (UTC+2)
+        let now = utc("2024-09-14T18:00:00Z");
+        assert_eq!(
+            business_hours().next_opening(now),
+            Some(utc("2024-09-16T07:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn opening_after_maintenance() {
+        let now = utc("2024-09-11T10:30:00Z");
+        assert_eq!(
+            business_hours().next_opening(now),
+            Some(utc("2024-09-11T12:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn overnight_maintenance_window() {
+        let schedule: DeliverySchedule = serde_json::from_value(json!({
+            "timezone": "UTC",
+            "maintenance_windows": [{ "days": ["sun"], "start": "22:00", "end": "02:00" }],
+        }))
+        .unwrap();
+        assert_eq!(
+            schedule.next_opening(utc("2024-09-16T01:00:00Z")),
+            Some(utc("2024-09-16T02:00:00Z"))
+        );
+        let now = utc("2024-09-16T03:00:00Z");
+        assert_eq!(schedule.next_opening(now), Some(now));
+    }
+
+    #[test]
+    fn never_open() {
+        let schedule: DeliverySchedule = serde_json::from_value(json!({
+            "timezone": "UTC",
+            "open_windows": [{ "days": ["mon"], "start": "09:00", "end": "10:00" }],
+            "maintenance_windows": [{ "days": ["mon"], "start": "08:00", "end": "11:00" }],
+        }))
+        .unwrap();
+        assert_eq!(schedule.next_opening(utc("2024-09-16T09:30:00Z")), None);
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        assert!(serde_json::from_value::<DeliverySchedule>(
+            json!({ "timezone": "Mars/Olympus_Mons" })
+        )
+        .is_err());
+        assert!(serde_json::from_value::<DeliverySchedule>(json!({
+            "timezone": "UTC",
+            "open_windows": [{ "days": ["mon"], "start": "9h", "end": "10:00" }],
+        }))
+        .is_err());
+
+        let schedule: DeliverySchedule = serde_json::from_value(json!({
+            "timezone": "UTC",
+            "open_windows": [{ "days": [], "start": "09:00", "end": "10:00" }],
+        }))
+        .unwrap();
+        assert!(schedule.check().is_err());
+    }
+}
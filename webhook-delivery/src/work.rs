@@ -0,0 +1,704 @@
+use chrono::{DateTime, Utc};
+use hex::ToHex;
+use hmac::{Hmac, Mac};
+use log::{debug, error, trace, warn};
+use payload_transformation::TransformationError;
+use reqwest::header::{HeaderMap, HeaderValue, InvalidHeaderValue};
+use reqwest::{Client, Method, Url};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use strum::VariantNames;
+use uuid::Uuid;
+
+use crate::{amqp, kafka, Config, RequestAttempt, Target};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+pub(crate) const TIMEOUT: Duration = Duration::from_secs(15);
+const ENDPOINT_VERIFICATION_EVENT_TYPE: &str = "hook0.endpoint_verification";
+
+#[derive(Debug, Clone, Copy, strum::Display, VariantNames)]
+pub enum ResponseError {
+    #[strum(serialize = "E_UNKNOWN")]
+    Unknown,
+    #[strum(serialize = "E_INVALID_TARGET")]
+    InvalidTarget,
+    #[strum(serialize = "E_CONNECTION")]
+    Connection,
+    #[strum(serialize = "E_TIMEOUT")]
+    Timeout,
+    #[strum(serialize = "E_HTTP")]
+    Http,
+    #[strum(serialize = "E_EXPIRED")]
+    Expired,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct HttpTarget {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub response_error: Option<ResponseError>,
+    pub http_code: Option<u16>,
+    pub headers: Option<HeaderMap>,
+    pub body: Option<String>,
+    pub elapsed_time: Duration,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        self.response_error.is_none()
+    }
+
+    #[allow(non_snake_case)]
+    pub fn response_error__name(&self) -> Option<String> {
+        self.response_error.map(|re| re.to_string())
+    }
+
+    pub fn http_code(&self) -> Option<i16> {
+        self.http_code.and_then(|c| c.try_into().ok())
+    }
+
+    pub fn headers(&self) -> Option<serde_json::Value> {
+        self.headers.as_ref().and_then(|hm| {
+            let iter = hm.iter().map(|(k, v)| {
+                let key = k.to_string();
+                let value = String::from_utf8_lossy(v.as_bytes()).into_owned();
+                (key, value)
+            });
+            let hashmap: HashMap<String, String> = iter.collect();
+            serde_json::to_value(hashmap).ok()
+        })
+    }
+
+    pub fn elapsed_time_ms(&self) -> i32 {
+        self.elapsed_time.as_millis().try_into().unwrap_or(0)
+    }
+}
+
+pub async fn work(config: &Config, attempt: &RequestAttempt) -> Response {
+    debug!(
+        "Processing request attempt {}",
+        &attempt.request_attempt__id
+    );
+    let start = Instant::now();
+
+    if let Some(response) = expired_response(attempt, start) {
+        return response;
+    }
+
+    match attempt.body() {
+        Ok((body, content_type)) => {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                "X-Event-Id",
+                HeaderValue::from_str(attempt.event__id.to_string().as_str())
+                    .expect("Could not create a header value from the event ID UUID"),
+            );
+            headers.insert(
+                "X-Event-Type",
+                HeaderValue::from_str(&attempt.event_type__name)
+                    .expect("Could not create a header value from the event type"),
+            );
+            send(config, attempt, body, &content_type, headers, start).await
+        }
+        Err(e) => invalid_transformation_response(e, start),
+    }
+}
+
+/// Send several request attempts of a same subscription in one request whose body is a JSON array (see [batch_body])
+pub async fn work_batch(
+    config: &Config,
+    attempts: &[RequestAttempt],
+    body: Result<Vec<u8>, TransformationError>,
+) -> Response {
+    let attempt = &attempts[0];
+    debug!(
+        "Processing batch of {} request attempts of subscription {}",
+        attempts.len(),
+        &attempt.subscription__id
+    );
+    let start = Instant::now();
+
+    if let Some(response) = expired_response(attempt, start) {
+        return response;
+    }
+
+    match body {
+        Ok(body) => {
+            let mut headers = HeaderMap::new();
+            headers.insert("X-Hook0-Batch-Size", HeaderValue::from(attempts.len()));
+            send(config, attempt, body, "application/json", headers, start).await
+        }
+        Err(e) => invalid_transformation_response(e, start),
+    }
+}
+
+/// Body of a batch request: a JSON array containing an item per attempt
+///
+/// Attempts that would make the body larger than the subscription's maximum batch size are removed from the list (but the first one is always kept).
+pub fn batch_body(attempts: &mut Vec<RequestAttempt>) -> Result<Vec<u8>, TransformationError> {
+    let max_size = attempts
+        .first()
+        .and_then(|a| a.batch_max_size_in_bytes)
+        .and_then(|s| usize::try_from(s).ok())
+        .unwrap_or(usize::MAX);
+
+    let mut body = vec![b'['];
+    let mut len = 0;
+    for attempt in attempts.iter() {
+        let item = attempt.batch_item()?.to_string();
+        // Separator and closing bracket must fit too
+        if len > 0 && body.len() + item.len() + 2 > max_size {
+            break;
+        }
+        if len > 0 {
+            body.push(b',');
+        }
+        body.extend_from_slice(item.as_bytes());
+        len += 1;
+    }
+    body.push(b']');
+
+    attempts.truncate(len);
+    Ok(body)
+}
+
+fn expired_response(attempt: &RequestAttempt, start: Instant) -> Option<Response> {
+    let expires_at = attempt.expires_at.filter(|_| attempt.has_expired())?;
+    warn!("Event expired at {expires_at}; webhook will not be called");
+    Some(Response {
+        response_error: Some(ResponseError::Expired),
+        http_code: None,
+        headers: None,
+        body: Some(format!("Event expired at {expires_at}")),
+        elapsed_time: start.elapsed(),
+    })
+}
+
+fn invalid_transformation_response(e: TransformationError, start: Instant) -> Response {
+    error!("Subscription has an invalid transformation: {}", &e);
+    Response {
+        response_error: Some(ResponseError::InvalidTarget),
+        http_code: None,
+        headers: None,
+        body: Some(e.to_string()),
+        elapsed_time: start.elapsed(),
+    }
+}
+
+/// Deliver a body to the target of an attempt's subscription, signed with the subscription's secret
+async fn send(
+    config: &Config,
+    attempt: &RequestAttempt,
+    body: Vec<u8>,
+    content_type: &str,
+    extra_headers: HeaderMap,
+    start: Instant,
+) -> Response {
+    let mut headers = HeaderMap::new();
+    let content_type = HeaderValue::from_str(content_type)
+        .expect("Could not create a header value from the event content type");
+    let sig = Signature::new(&attempt.secret.to_string(), &body, Utc::now())
+        .to_header_value()
+        .expect("Could not create a header value from the event ID UUID");
+    headers.insert("Content-Type", content_type);
+    headers.extend(extra_headers);
+    headers.insert("X-Hook0-Signature", sig);
+
+    let delivery = match attempt.target() {
+        Ok(Target::Http(target)) => {
+            return send_http(config, &target, body, headers, start).await;
+        }
+        Ok(Target::Amqp(target)) => {
+            debug!("Publishing to AMQP broker...");
+            amqp::publish(config, attempt, &target, &body, &headers).await
+        }
+        Ok(Target::Kafka(target)) => {
+            debug!("Producing to Kafka...");
+            kafka::produce(config, attempt, &target, body, &headers).await
+        }
+        Err(e) => Err((ResponseError::InvalidTarget, e.to_string())),
+    };
+
+    match delivery {
+        Ok(body) => {
+            debug!("Delivery to broker was successful");
+            Response {
+                response_error: None,
+                http_code: None,
+                headers: None,
+                body: Some(body),
+                elapsed_time: start.elapsed(),
+            }
+        }
+        Err((response_error, e)) => {
+            match response_error {
+                ResponseError::InvalidTarget => error!("Target is invalid: {}", &e),
+                _ => warn!("Delivery to broker failed: {}", &e),
+            }
+            Response {
+                response_error: Some(response_error),
+                http_code: None,
+                headers: None,
+                body: Some(e),
+                elapsed_time: start.elapsed(),
+            }
+        }
+    }
+}
+
+/// Check that a target does not resolve only to IPs that are not globally reachable (unless it is allowed in the worker's configuration)
+pub(crate) fn check_target_addrs(config: &Config, addrs: &[SocketAddr]) -> Result<(), String> {
+    if addrs.iter().all(|addr| is_forbidden_ip(&addr.ip())) {
+        if config.disable_target_ip_check {
+            debug!("Target resolves to a forbidden IP but this is allowed in the worker's configuration");
+            Ok(())
+        } else {
+            Err("Target resolves to a forbidden IP".to_owned())
+        }
+    } else {
+        Ok(())
+    }
+}
+
+fn is_forbidden_ip(ip: &IpAddr) -> bool {
+    // This should be replaced by https://doc.rust-lang.org/nightly/core/net/enum.IpAddr.html#method.is_global when it becomes stable
+
+    // v4
+    fn is_shared(ip: &Ipv4Addr) -> bool {
+        ip.octets()[0] == 100 && (ip.octets()[1] & 0b1100_0000 == 0b0100_0000)
+    }
+    fn is_benchmarking(ip: &Ipv4Addr) -> bool {
+        ip.octets()[0] == 198 && (ip.octets()[1] & 0xfe) == 18
+    }
+    fn is_reserved(ip: &Ipv4Addr) -> bool {
+        ip.octets()[0] & 240 == 240 && !ip.is_broadcast()
+    }
+
+    // v6
+    fn is_documentation(ip: &Ipv6Addr) -> bool {
+        (ip.segments()[0] == 0x2001) && (ip.segments()[1] == 0xdb8)
+    }
+    fn is_unique_local(ip: &Ipv6Addr) -> bool {
+        (ip.segments()[0] & 0xfe00) == 0xfc00
+    }
+    fn is_unicast_link_local(ip: &Ipv6Addr) -> bool {
+        (ip.segments()[0] & 0xffc0) == 0xfe80
+    }
+
+    match *ip {
+        IpAddr::V4(ip) => {
+            ip.octets()[0] == 0 // "This network"
+                || ip.is_private()
+                || is_shared(&ip)
+                || ip.is_loopback()
+                || ip.is_link_local()
+                // addresses reserved for future protocols (`192.0.0.0/24`)
+                ||(ip.octets()[0] == 192 && ip.octets()[1] == 0 && ip.octets()[2] == 0)
+                || ip.is_documentation()
+                || is_benchmarking(&ip)
+                || is_reserved(&ip)
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => {
+            ip.is_unspecified()
+                || ip.is_loopback()
+                // IPv4-mapped Address (`::ffff:0:0/96`)
+                || matches!(ip.segments(), [0, 0, 0, 0, 0, 0xffff, _, _])
+                // IPv4-IPv6 Translat. (`64:ff9b:1::/48`)
+                || matches!(ip.segments(), [0x64, 0xff9b, 1, _, _, _, _, _])
+                // Discard-Only Address Block (`100::/64`)
+                || matches!(ip.segments(), [0x100, 0, 0, 0, _, _, _, _])
+                // IETF Protocol Assignments (`2001::/23`)
+                || (matches!(ip.segments(), [0x2001, b, _, _, _, _, _, _] if b < 0x200)
+                    && !(
+                        // Port Control Protocol Anycast (`2001:1::1`)
+                        u128::from_be_bytes(ip.octets()) == 0x2001_0001_0000_0000_0000_0000_0000_0001
+                        // Traversal Using Relays around NAT Anycast (`2001:1::2`)
+                        || u128::from_be_bytes(ip.octets()) == 0x2001_0001_0000_0000_0000_0000_0000_0002
+                        // AMT (`2001:3::/32`)
+                        || matches!(ip.segments(), [0x2001, 3, _, _, _, _, _, _])
+                        // AS112-v6 (`2001:4:112::/48`)
+                        || matches!(ip.segments(), [0x2001, 4, 0x112, _, _, _, _, _])
+                        // ORCHIDv2 (`2001:20::/28`)
+                        || matches!(ip.segments(), [0x2001, b, _, _, _, _, _, _] if (0x20..=0x2F).contains(&b))
+                    ))
+                || is_documentation(&ip)
+                || is_unique_local(&ip)
+                || is_unicast_link_local(&ip)
+        }
+    }
+}
+
+/// Call an HTTP target
+async fn send_http(
+    config: &Config,
+    target: &HttpTarget,
+    body: Vec<u8>,
+    headers: HeaderMap,
+    start: Instant,
+) -> Response {
+    let m = Method::from_str(target.method.as_str());
+    let u = Url::parse(target.url.as_str())
+        .map_err(|e| e.to_string())
+        .and_then(|url| {
+            let addrs = url.socket_addrs(|| None).map_err(|e| e.to_string())?;
+            check_target_addrs(config, &addrs)?;
+            Ok(url)
+        });
+    let c = mk_http_client(config);
+    let hs = HeaderMap::try_from(&target.headers);
+
+    match (m, u, c, hs) {
+        (Ok(method), Ok(url), Ok(client), Ok(mut target_headers)) => {
+            target_headers.extend(headers);
+            let headers = target_headers;
+
+            debug!("Calling webhook...");
+            trace!(
+                "HTTP {} {} {:?}",
+                &method.to_string().to_uppercase(),
+                &url,
+                &headers
+            );
+            let response = client
+                .request(method, url)
+                .headers(headers)
+                .body(body)
+                .send()
+                .await;
+
+            match response {
+                Ok(res) => {
+                    let status = res.status();
+                    let headers = res.headers().clone();
+                    let body = res.text().await.ok();
+
+                    if status.is_success() {
+                        debug!("Webhook call was successful");
+                        Response {
+                            response_error: None,
+                            http_code: Some(status.as_u16()),
+                            headers: Some(headers),
+                            body,
+                            elapsed_time: start.elapsed(),
+                        }
+                    } else {
+                        warn!("Webhook call failed with HTTP code {}", &status);
+                        Response {
+                            response_error: Some(ResponseError::Http),
+                            http_code: Some(status.as_u16()),
+                            headers: Some(headers),
+                            body,
+                            elapsed_time: start.elapsed(),
+                        }
+                    }
+                }
+                Err(e) if e.is_connect() => {
+                    warn!("Webhook call failed with connection error: {}", &e);
+                    Response {
+                        response_error: Some(ResponseError::Connection),
+                        http_code: None,
+                        headers: None,
+                        body: Some(e.to_string()),
+                        elapsed_time: start.elapsed(),
+                    }
+                }
+                Err(e) if e.is_timeout() => {
+                    warn!("Webhook call failed with timeout error: {}", &e);
+                    Response {
+                        response_error: Some(ResponseError::Timeout),
+                        http_code: None,
+                        headers: None,
+                        body: Some(e.to_string()),
+                        elapsed_time: start.elapsed(),
+                    }
+                }
+                Err(e) => {
+                    warn!("Webhook call failed with unknown error: {}", &e);
+                    Response {
+                        response_error: Some(ResponseError::Unknown),
+                        http_code: None,
+                        headers: None,
+                        body: Some(e.to_string()),
+                        elapsed_time: start.elapsed(),
+                    }
+                }
+            }
+        }
+        (Err(e), _, _, _) => {
+            error!("Target has an invalid HTTP method: {}", &e);
+            Response {
+                response_error: Some(ResponseError::InvalidTarget),
+                http_code: None,
+                headers: None,
+                body: Some(e.to_string()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+        (_, Err(e), _, _) => {
+            error!("Target has an invalid URL: {}", &e);
+            Response {
+                response_error: Some(ResponseError::InvalidTarget),
+                http_code: None,
+                headers: None,
+                body: Some(e.to_string()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+        (_, _, Err(e), _) => {
+            error!("Could not create HTTP client: {}", &e);
+            Response {
+                response_error: Some(ResponseError::Unknown),
+                http_code: None,
+                headers: None,
+                body: Some(e.to_string()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+        (_, _, _, Err(e)) => {
+            error!("Target has invalid headers: {}", &e);
+            Response {
+                response_error: Some(ResponseError::InvalidTarget),
+                http_code: None,
+                headers: None,
+                body: Some(e.to_string()),
+                elapsed_time: start.elapsed(),
+            }
+        }
+    }
+}
+
+/// Why the HTTP endpoint of a subscription could not be verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointVerificationError {
+    /// The endpoint could not be reached or answered with an error that may go away (HTTP 429 or 5xx); verification can be tried again
+    Transient(String),
+    /// The endpoint answered but did not pass verification
+    Failed(String),
+}
+
+impl EndpointVerificationError {
+    pub fn message(&self) -> &str {
+        match self {
+            Self::Transient(message) | Self::Failed(message) => message,
+        }
+    }
+}
+
+/// Send a challenge to the HTTP endpoint of a subscription, which must answer successfully and echo it (either as the whole body or as the `challenge` property of a JSON object)
+pub async fn verify_endpoint(
+    config: &Config,
+    target: &HttpTarget,
+    secret: &Uuid,
+    challenge: &str,
+) -> Result<(), EndpointVerificationError> {
+    debug!("Verifying endpoint {}", &target.url);
+    let start = Instant::now();
+
+    let body = serde_json::to_vec(&serde_json::json!({
+        "type": ENDPOINT_VERIFICATION_EVENT_TYPE,
+        "challenge": challenge,
+    }))
+    .expect("Could not serialize endpoint verification body");
+    let mut headers = HeaderMap::new();
+    headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+    headers.insert(
+        "X-Event-Type",
+        HeaderValue::from_static(ENDPOINT_VERIFICATION_EVENT_TYPE),
+    );
+    let sig = Signature::new(&secret.to_string(), &body, Utc::now())
+        .to_header_value()
+        .expect("Could not create a header value from the signature");
+    headers.insert("X-Hook0-Signature", sig);
+
+    let response = send_http(config, target, body, headers, start).await;
+    match (response.response_error, response.http_code) {
+        (Some(e), Some(code)) => {
+            let message = format!("Endpoint answered with HTTP code {code} ({e})");
+            if code == 429 || code >= 500 {
+                Err(EndpointVerificationError::Transient(message))
+            } else {
+                Err(EndpointVerificationError::Failed(message))
+            }
+        }
+        (Some(e), None) => {
+            let message = format!(
+                "Endpoint could not be called ({e}): {}",
+                response.body.unwrap_or_default()
+            );
+            if matches!(e, ResponseError::InvalidTarget) {
+                Err(EndpointVerificationError::Failed(message))
+            } else {
+                Err(EndpointVerificationError::Transient(message))
+            }
+        }
+        (None, _) if echoes_challenge(response.body.as_deref().unwrap_or(""), challenge) => Ok(()),
+        (None, _) => Err(EndpointVerificationError::Failed(
+            "Endpoint answered successfully but did not echo the challenge".to_owned(),
+        )),
+    }
+}
+
+fn echoes_challenge(body: &str, challenge: &str) -> bool {
+    body.trim() == challenge
+        || serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|json| {
+                json.get("challenge")
+                    .and_then(|c| c.as_str())
+                    .map(|c| c == challenge)
+            })
+            .unwrap_or(false)
+}
+
+fn mk_http_client(config: &Config) -> reqwest::Result<Client> {
+    Client::builder()
+        .connection_verbose(true)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(TIMEOUT)
+        .user_agent(&config.user_agent)
+        .tcp_keepalive(None)
+        .build()
+}
+
+struct Signature {
+    pub timestamp: i64,
+    pub v0: String,
+}
+
+impl Signature {
+    const PAYLOAD_SEPARATOR: &'static [u8] = b".";
+    const SIGNATURE_PART_ASSIGNATOR: &'static str = "=";
+    const SIGNATURE_PART_SEPARATOR: &'static str = ",";
+
+    pub fn new(secret: &str, payload: &[u8], signed_at: DateTime<Utc>) -> Self {
+        let timestamp = signed_at.timestamp();
+        let timestamp_str = timestamp.to_string();
+        let timestamp_str_bytes = timestamp_str.as_bytes();
+
+        type HmacSha256 = Hmac<Sha256>;
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap(); // MAC can take key of any size; this should never fail
+        mac.update(timestamp_str_bytes);
+        mac.update(Self::PAYLOAD_SEPARATOR);
+        mac.update(payload);
+        let v0 = mac.finalize().into_bytes().encode_hex::<String>();
+
+        Self { timestamp, v0 }
+    }
+
+    pub fn value(&self) -> String {
+        let timestamp_str = self.timestamp.to_string();
+        let parts = &[("t", timestamp_str.as_str()), ("v0", self.v0.as_str())];
+
+        itertools::Itertools::intersperse(
+            parts
+                .iter()
+                .map(|p| format!("{}{}{}", p.0, Self::SIGNATURE_PART_ASSIGNATOR, p.1)),
+            Self::SIGNATURE_PART_SEPARATOR.to_owned(),
+        )
+        .collect::<String>()
+    }
+
+    pub fn to_header_value(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        HeaderValue::from_str(&self.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::prelude::*;
+
+    #[test]
+    fn endpoint_echoes_challenge() {
+        assert!(echoes_challenge("abc123\n", "abc123"));
+        assert!(echoes_challenge(r#"{"challenge": "abc123"}"#, "abc123"));
+        assert!(!echoes_challenge(r#"{"challenge": "abc"}"#, "abc123"));
+        assert!(!echoes_challenge("OK", "abc123"));
+    }
+
+    #[test]
+    fn create_signature() {
+        let signed_at = Utc.with_ymd_and_hms(2021, 11, 15, 0, 30, 0).unwrap();
+        let payload = "hello !";
+        let secret = "secret";
+
+        let sig = Signature::new(secret, payload.as_bytes(), signed_at);
+        assert_eq!(
+            sig.value(),
+            "t=1636936200,v0=1b3d69df55f1e52f05224ba94a5162abeb17ef52cd7f4948c390f810d6a87e98"
+        );
+    }
+
+    fn mk_attempt(payload: &str, batch_max_size_in_bytes: Option<i32>) -> RequestAttempt {
+        RequestAttempt {
+            request_attempt__id: uuid::Uuid::new_v4(),
+            event__id: uuid::Uuid::nil(),
+            subscription__id: uuid::Uuid::nil(),
+            created_at: Utc::now(),
+            retry_count: 0,
+            target: serde_json::json!({
+                "type": "http",
+                "method": "POST",
+                "url": "https://www.example.com",
+                "headers": {},
+            }),
+            event_type__name: "billing.invoice.paid".to_owned(),
+            payload: payload.as_bytes().to_vec(),
+            payload_content_type: "text/plain".to_owned(),
+            occurred_at: Utc.with_ymd_and_hms(2024, 8, 5, 9, 0, 0).unwrap(),
+            labels: serde_json::json!({}),
+            metadata: None,
+            secret: uuid::Uuid::nil(),
+            transformation: Some(serde_json::json!({ "text": "{{ payload }}" })),
+            batch_max_count: Some(10),
+            batch_max_size_in_bytes,
+            expires_at: None,
+            delivery_schedule: None,
+            max_requests_per_second: None,
+            max_in_flight_requests: None,
+        }
+    }
+
+    #[test]
+    fn defer_attempts_with_invalid_delivery_schedule() {
+        let now = Utc.with_ymd_and_hms(2024, 9, 16, 12, 0, 0).unwrap();
+        let mut attempt = mk_attempt("a", None);
+        assert_eq!(attempt.deferred_until(now), None);
+
+        attempt.delivery_schedule = Some(serde_json::json!({ "timezone": "Mars/Olympus_Mons" }));
+        assert_eq!(
+            attempt.deferred_until(now),
+            Some(now + chrono::Duration::days(7))
+        );
+    }
+
+    #[test]
+    fn create_batch_body() {
+        let mut attempts = vec![mk_attempt("a", None), mk_attempt("b", None)];
+        assert_eq!(
+            batch_body(&mut attempts).unwrap(),
+            br#"[{"text":"a"},{"text":"b"}]"#
+        );
+        assert_eq!(attempts.len(), 2);
+
+        // Second item would make the body 27 bytes long
+        let mut attempts = vec![mk_attempt("a", Some(26)), mk_attempt("b", Some(26))];
+        assert_eq!(batch_body(&mut attempts).unwrap(), br#"[{"text":"a"}]"#);
+        assert_eq!(attempts.len(), 1);
+
+        // First item is always sent
+        let mut attempts = vec![mk_attempt("a", Some(1))];
+        assert_eq!(batch_body(&mut attempts).unwrap(), br#"[{"text":"a"}]"#);
+    }
+}